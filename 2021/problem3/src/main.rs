@@ -1,39 +1,345 @@
 use anyhow::{anyhow, bail, Context, Result};
 use std::fs::File;
 use std::io::{BufRead, BufReader};
+use std::str::FromStr;
 
-fn most_common_bit(bits: &[char]) -> char {
-    // If bits are equally common, return 1.
-    let count = bits.iter().filter(|&&b| b == '1').count();
-    if count >= bits.len() - count {
-        '1'
-    } else {
-        '0'
+#[derive(Copy, Clone, Debug)]
+enum TieBreak {
+    PreferHighest,
+    PreferLowest,
+    Error,
+}
+
+impl FromStr for TieBreak {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<TieBreak> {
+        match s {
+            "1" | "high" => Ok(TieBreak::PreferHighest),
+            "0" | "low" => Ok(TieBreak::PreferLowest),
+            "error" => Ok(TieBreak::Error),
+            _ => bail!("Unrecognized tie-break policy '{}'.", s),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+enum ReportFormat {
+    Table,
+    Json,
+}
+
+impl FromStr for ReportFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<ReportFormat> {
+        match s {
+            "table" => Ok(ReportFormat::Table),
+            "json" => Ok(ReportFormat::Json),
+            _ => bail!("Unrecognized report format '{}'.", s),
+        }
+    }
+}
+
+struct Options {
+    filename: String,
+    num_digits: usize,
+    radix: u32,
+    o2_tie_break: TieBreak,
+    co2_tie_break: TieBreak,
+    trace: bool,
+    report: Option<ReportFormat>,
+    margin: usize,
+}
+
+impl Options {
+    fn parse(args: impl Iterator<Item = String>) -> Result<Options> {
+        let mut args = args.skip(1);
+        let mut positional = Vec::new();
+        let mut radix = 2;
+        let mut o2_tie_break = TieBreak::PreferHighest;
+        let mut co2_tie_break = TieBreak::PreferLowest;
+        let mut trace = false;
+        let mut report = None;
+        let mut margin = 0;
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--radix" => {
+                    let radix_str =
+                        args.next().ok_or(anyhow!("No radix provided."))?;
+                    radix = radix_str.parse::<u32>().with_context(|| {
+                        format!(
+                            "Failed to parse '{}' as unsigned integer.",
+                            radix_str
+                        )
+                    })?;
+                    if !(2..=36).contains(&radix) {
+                        bail!("Radix {} is not between 2 and 36.", radix);
+                    }
+                }
+                "--o2-tie-break" => {
+                    o2_tie_break = args
+                        .next()
+                        .ok_or(anyhow!("No O2 tie-break policy provided."))?
+                        .parse()?;
+                }
+                "--co2-tie-break" => {
+                    co2_tie_break = args
+                        .next()
+                        .ok_or(anyhow!("No CO2 tie-break policy provided."))?
+                        .parse()?;
+                }
+                "--trace" => trace = true,
+                "--report" => {
+                    report = Some(
+                        args.next()
+                            .ok_or(anyhow!("No report format provided."))?
+                            .parse()?,
+                    );
+                }
+                "--margin" => {
+                    let margin_str =
+                        args.next().ok_or(anyhow!("No margin provided."))?;
+                    margin =
+                        margin_str.parse::<usize>().with_context(|| {
+                            format!(
+                                "Failed to parse '{}' as unsigned integer.",
+                                margin_str
+                            )
+                        })?;
+                }
+                _ if arg.starts_with("--") => {
+                    bail!("Unrecognized option '{}'.", arg)
+                }
+                _ => positional.push(arg),
+            }
+        }
+
+        let mut positional = positional.into_iter();
+        let filename =
+            positional.next().ok_or(anyhow!("No filename provided."))?;
+        let num_digits_str = positional
+            .next()
+            .ok_or(anyhow!("No number of digits provided."))?;
+        let num_digits =
+            num_digits_str.parse::<usize>().with_context(|| {
+                format!(
+                    "Failed to parse '{}' as unsigned integer.",
+                    num_digits_str
+                )
+            })?;
+        if let Some(arg) = positional.next() {
+            bail!("Unexpected argument '{}'.", arg);
+        }
+
+        Ok(Options {
+            filename,
+            num_digits,
+            radix,
+            o2_tie_break,
+            co2_tie_break,
+            trace,
+            report,
+            margin,
+        })
+    }
+}
+
+fn digit_counts(digits: &[u32], radix: u32) -> Vec<usize> {
+    let mut counts = vec![0; radix as usize];
+    for &d in digits {
+        counts[d as usize] += 1;
+    }
+    counts
+}
+
+/// Counts of each digit at each position, gathered in a single pass over the
+/// reports.
+struct Histogram {
+    counts: Vec<Vec<usize>>,
+}
+
+impl Histogram {
+    fn new(reports: &[Vec<u32>], num_digits: usize, radix: u32) -> Self {
+        let mut counts = vec![vec![0; radix as usize]; num_digits];
+        for report in reports {
+            for (position, &d) in report.iter().enumerate() {
+                counts[position][d as usize] += 1;
+            }
+        }
+        Histogram { counts }
+    }
+
+    /// Returns the difference between the counts of the two most common
+    /// digits at the given position.
+    fn split(&self, position: usize) -> usize {
+        let mut counts = self.counts[position].clone();
+        counts.sort_unstable_by(|a, b| b.cmp(a));
+        counts[0] - counts.get(1).copied().unwrap_or(0)
+    }
+
+    fn fragile_positions(&self, margin: usize) -> Vec<usize> {
+        (0..self.counts.len())
+            .filter(|&position| self.split(position) <= margin)
+            .collect()
+    }
+
+    fn print_table(&self, radix: u32, margin: usize) {
+        print!("position");
+        for d in 0..radix {
+            print!("\t{}", std::char::from_digit(d, radix).unwrap());
+        }
+        println!("\tsplit\tfragile");
+        for (position, counts) in self.counts.iter().enumerate() {
+            print!("{}", position);
+            for count in counts {
+                print!("\t{}", count);
+            }
+            let split = self.split(position);
+            println!(
+                "\t{}\t{}",
+                split,
+                if split <= margin { "yes" } else { "no" }
+            );
+        }
+    }
+
+    fn print_json(&self, radix: u32, margin: usize) {
+        let join = |values: Vec<String>| values.join(",");
+        let positions = self
+            .counts
+            .iter()
+            .enumerate()
+            .map(|(position, counts)| {
+                let split = self.split(position);
+                format!(
+                    "{{\"position\":{},\"counts\":[{}],\"split\":{},\
+                     \"fragile\":{}}}",
+                    position,
+                    join(counts.iter().map(|c| c.to_string()).collect()),
+                    split,
+                    split <= margin
+                )
+            })
+            .collect();
+        let fragile_positions = self
+            .fragile_positions(margin)
+            .iter()
+            .map(|p| p.to_string())
+            .collect();
+        println!(
+            "{{\"radix\":{},\"margin\":{},\"positions\":[{}],\
+             \"fragile_positions\":[{}]}}",
+            radix,
+            margin,
+            join(positions),
+            join(fragile_positions)
+        );
+    }
+}
+
+fn break_tie(candidates: &[u32], tie_break: TieBreak) -> Result<u32> {
+    match (candidates, tie_break) {
+        ([digit], _) => Ok(*digit),
+        (_, TieBreak::PreferHighest) => Ok(*candidates.iter().max().unwrap()),
+        (_, TieBreak::PreferLowest) => Ok(*candidates.iter().min().unwrap()),
+        (_, TieBreak::Error) => {
+            bail!("Digits {:?} are equally common.", candidates)
+        }
+    }
+}
+
+fn most_common_digit(counts: &[usize], tie_break: TieBreak) -> Result<u32> {
+    let max_count = counts.iter().copied().max().unwrap_or(0);
+    let candidates: Vec<u32> = (0..counts.len() as u32)
+        .filter(|&d| counts[d as usize] == max_count)
+        .collect();
+    break_tie(&candidates, tie_break)
+}
+
+fn least_common_digit(counts: &[usize], tie_break: TieBreak) -> Result<u32> {
+    // Digits that never occur count as the least common.
+    let min_count = counts.iter().copied().min().unwrap_or(0);
+    let candidates: Vec<u32> = (0..counts.len() as u32)
+        .filter(|&d| counts[d as usize] == min_count)
+        .collect();
+    break_tie(&candidates, tie_break)
+}
+
+fn least_common_occurring_digit(
+    counts: &[usize],
+    tie_break: TieBreak,
+) -> Result<u32> {
+    // Only digits that actually occur are considered, so that filtering on
+    // the digit always leaves a candidate.
+    let min_count = counts.iter().copied().filter(|&c| c > 0).min();
+    let candidates: Vec<u32> = (0..counts.len() as u32)
+        .filter(|&d| Some(counts[d as usize]) == min_count)
+        .collect();
+    if candidates.is_empty() {
+        bail!("No digits provided.");
     }
+    break_tie(&candidates, tie_break)
+}
+
+fn digits_to_value(digits: &[u32], radix: u32) -> Result<usize> {
+    digits.iter().try_fold(0usize, |value, &d| {
+        value
+            .checked_mul(radix as usize)
+            .and_then(|v| v.checked_add(d as usize))
+            .ok_or(anyhow!("Value overflowed while converting digits."))
+    })
 }
 
-fn least_common_bit(bits: &[char]) -> char {
-    // If bits are equally common, return 0.
-    let count = bits.iter().filter(|&&b| b == '0').count();
-    println!("{}, {}", bits.len(), count);
-    if count <= bits.len() - count {
-        '0'
-    } else {
-        '1'
+type DigitSelector = fn(&[usize], TieBreak) -> Result<u32>;
+
+fn rating(
+    name: &str,
+    reports: &[Vec<u32>],
+    options: &Options,
+    select_digit: DigitSelector,
+    tie_break: TieBreak,
+) -> Result<usize> {
+    let mut values: Vec<&[u32]> =
+        reports.iter().map(|r| r.as_slice()).collect();
+    for i in 0..options.num_digits {
+        if values.len() > 1 {
+            let digit = select_digit(
+                &digit_counts(
+                    &values.iter().map(|r| r[i]).collect::<Vec<u32>>(),
+                    options.radix,
+                ),
+                tie_break,
+            )
+            .with_context(|| {
+                format!("Failed to select {} digit in position {}.", name, i)
+            })?;
+            let num_candidates = values.len();
+            values.retain(|r| r[i] == digit);
+            if options.trace {
+                println!(
+                    "{} position {}: {} candidates, kept {}, {} remain",
+                    name,
+                    i,
+                    num_candidates,
+                    std::char::from_digit(digit, options.radix).unwrap(),
+                    values.len()
+                );
+            }
+        } else {
+            break;
+        }
     }
+    digits_to_value(
+        values.first().ok_or(anyhow!("No reports provided."))?,
+        options.radix,
+    )
 }
 
 fn main() -> Result<()> {
-    let filename = std::env::args()
-        .nth(1)
-        .ok_or(anyhow!("No filename provided."))?;
-    let num_bits_str = std::env::args()
-        .nth(2)
-        .ok_or(anyhow!("No number of bits provided."))?;
-    let num_bits = num_bits_str.parse::<usize>().with_context(|| {
-        format!("Failed to parse '{}' as unsigned integer.", num_bits_str)
-    })?;
-    let file = File::open(filename).context("Failed to open file.")?;
+    let options = Options::parse(std::env::args())?;
+    let (num_digits, radix) = (options.num_digits, options.radix);
+    let file = File::open(&options.filename).context("Failed to open file.")?;
     let reader = BufReader::new(file);
 
     let lines = reader
@@ -41,67 +347,73 @@ fn main() -> Result<()> {
         .collect::<Result<Vec<String>, _>>()
         .context("Failed to read line.")?;
 
+    let mut reports = Vec::new();
     for line in lines.iter() {
-        if line.chars().any(|c| c != '0' && c != '1') {
-            bail!("{} is not a binary number.", line);
-        }
-        if line.len() != num_bits {
-            bail!("Unexpected number of bits in {}.", line);
+        let digits = line
+            .chars()
+            .map(|c| c.to_digit(radix))
+            .collect::<Option<Vec<u32>>>()
+            .ok_or_else(|| {
+                anyhow!("{} is not a base {} number.", line, radix)
+            })?;
+        if digits.len() != num_digits {
+            bail!("Unexpected number of digits in {}.", line);
         }
+        reports.push(digits);
     }
 
-    // Calculate gamma and epsilon.
-    let mut gamma = 0;
-    let mut epsilon = 0;
-    for i in 0..num_bits {
-        gamma *= 2;
-        epsilon *= 2;
-
-        let bits_in_ith_position: Vec<char> =
-            lines.iter().map(|s| s.chars().nth(i).unwrap()).collect();
-        if most_common_bit(&bits_in_ith_position) == '1' {
-            gamma += 1;
+    let histogram = Histogram::new(&reports, num_digits, radix);
+    match options.report {
+        Some(ReportFormat::Table) => {
+            histogram.print_table(radix, options.margin);
+            return Ok(());
         }
-        if least_common_bit(&bits_in_ith_position) == '1' {
-            epsilon += 1;
+        Some(ReportFormat::Json) => {
+            histogram.print_json(radix, options.margin);
+            return Ok(());
         }
+        None => (),
     }
 
-    // Calculate O2 and CO2 ratings.
-    let mut o2_values: Vec<&str> = lines.iter().map(|s| s.as_str()).collect();
-    for i in 0..num_bits {
-        if o2_values.len() > 1 {
-            let o2_bit = most_common_bit(
-                &o2_values
-                    .iter()
-                    .map(|s| s.chars().nth(i).unwrap())
-                    .collect::<Vec<char>>(),
-            );
-            o2_values.retain(|s| s.chars().nth(i) == Some(o2_bit));
-        } else {
-            break;
-        }
-    }
-    let o2_rating = usize::from_str_radix(o2_values[0], 2)?;
-
-    let mut co2_values: Vec<&str> = lines.iter().map(|s| s.as_str()).collect();
-    for i in 0..num_bits {
-        if co2_values.len() > 1 {
-            let co2_bit = least_common_bit(
-                &co2_values
-                    .iter()
-                    .map(|s| s.chars().nth(i).unwrap())
-                    .collect::<Vec<char>>(),
-            );
-            co2_values.retain(|v| v.chars().nth(i) == Some(co2_bit));
-        } else {
-            break;
-        }
+    // Calculate gamma and epsilon.
+    let mut gamma_digits = Vec::new();
+    let mut epsilon_digits = Vec::new();
+    for counts in histogram.counts.iter() {
+        gamma_digits.push(most_common_digit(counts, TieBreak::PreferHighest)?);
+        epsilon_digits
+            .push(least_common_digit(counts, TieBreak::PreferLowest)?);
     }
-    let co2_rating = usize::from_str_radix(co2_values[0], 2)?;
+    let gamma = digits_to_value(&gamma_digits, radix)?;
+    let epsilon = digits_to_value(&epsilon_digits, radix)?;
+
+    // Calculate O2 and CO2 ratings.
+    let o2_rating = rating(
+        "O2",
+        &reports,
+        &options,
+        most_common_digit,
+        options.o2_tie_break,
+    )?;
+    let co2_rating = rating(
+        "CO2",
+        &reports,
+        &options,
+        least_common_occurring_digit,
+        options.co2_tie_break,
+    )?;
 
-    println!("gamma * epsilon: {}", gamma * epsilon);
-    println!("o2_rating * co2_rating: {}", o2_rating * co2_rating);
+    println!(
+        "gamma * epsilon: {}",
+        gamma
+            .checked_mul(epsilon)
+            .ok_or(anyhow!("gamma * epsilon overflowed."))?
+    );
+    println!(
+        "o2_rating * co2_rating: {}",
+        o2_rating
+            .checked_mul(co2_rating)
+            .ok_or(anyhow!("o2_rating * co2_rating overflowed."))?
+    );
 
     Ok(())
 }
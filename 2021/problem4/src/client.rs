@@ -0,0 +1,86 @@
+use crate::{rules, Board, BoardSize, Game};
+use anyhow::{anyhow, bail, Context, Result};
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+
+/// Plays a game against a server started with `serve`, claiming a win as
+/// soon as the board is complete. See the `server` module for the protocol.
+pub fn run(address: &str, colour: bool) -> Result<()> {
+    let stream = TcpStream::connect(address)
+        .with_context(|| format!("Failed to connect to {}.", address))?;
+    let mut writer = stream.try_clone()?;
+    let mut lines = BufReader::new(stream).lines();
+    let mut next_line = || -> Result<String> {
+        lines
+            .next()
+            .ok_or(anyhow!("Server closed the connection."))?
+            .context("Failed to read line.")
+    };
+    let mut expect = |prefix: &str| -> Result<String> {
+        let line = next_line()?;
+        line.strip_prefix(prefix)
+            .map(|s| s.to_string())
+            .ok_or_else(|| anyhow!("Expected {}, got '{}'.", prefix, line))
+    };
+
+    let player = expect("WELCOME ")?;
+    let rule = expect("RULE ")?;
+    let size = expect("BOARD ")?.replace(' ', "x").parse::<BoardSize>()?;
+    let rows = (0..size.rows)
+        .map(|_| expect(""))
+        .collect::<Result<Vec<_>>>()?;
+    let board = Board::parse(
+        &rows.iter().map(|s| s.as_str()).collect::<Vec<_>>(),
+        size,
+        1,
+    )
+    .context("Failed to parse bingo board sent by the server.")?;
+
+    let mut game = Game::new(vec![board], rules::parse(&rule, size)?);
+    println!(
+        "You are player {}, playing by '{}'. Your board:",
+        player, rule
+    );
+    println!("{}", game.boards[0].display(colour));
+
+    let mut claimed = false;
+    loop {
+        let line = expect("")?;
+        let (command, argument) = line.split_once(' ').unwrap_or((&line, ""));
+        match command {
+            "DRAW" => {
+                let number = argument.parse::<usize>().with_context(|| {
+                    format!("Failed to parse {} as integer.", argument)
+                })?;
+                println!("Drew {}.", number);
+                game.draw(number);
+                if game.boards[0].complete() && !claimed {
+                    println!("BINGO!");
+                    println!("{}", game.boards[0].display(colour));
+                    writeln!(writer, "BINGO")?;
+                    claimed = true;
+                }
+            }
+            "REJECTED" => println!("Claim rejected."),
+            "WINNER" => {
+                let (winner, score) = argument
+                    .split_once(' ')
+                    .ok_or_else(|| anyhow!("Malformed message '{}'.", line))?;
+                if winner == player {
+                    println!("You win with a score of {}!", score);
+                } else {
+                    println!(
+                        "Player {} wins with a score of {}.",
+                        winner, score
+                    );
+                }
+                return Ok(());
+            }
+            "GAMEOVER" => {
+                println!("Out of numbers, no one won.");
+                return Ok(());
+            }
+            _ => bail!("Unexpected message '{}'.", line),
+        }
+    }
+}
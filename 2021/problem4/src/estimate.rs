@@ -0,0 +1,163 @@
+use crate::rng::Rng;
+use crate::Game;
+
+/// Per-board tallies over a batch of simulated games.
+#[derive(Clone)]
+struct Tally {
+    first: Vec<u64>,
+    last: Vec<u64>,
+    wins: Vec<u64>,
+    draw_index_sum: Vec<u64>,
+}
+
+impl Tally {
+    fn new(num_boards: usize) -> Tally {
+        Tally {
+            first: vec![0; num_boards],
+            last: vec![0; num_boards],
+            wins: vec![0; num_boards],
+            draw_index_sum: vec![0; num_boards],
+        }
+    }
+
+    fn merge(&mut self, other: &Tally) {
+        let add = |a: &mut Vec<u64>, b: &Vec<u64>| {
+            a.iter_mut().zip(b).for_each(|(a, b)| *a += b)
+        };
+        add(&mut self.first, &other.first);
+        add(&mut self.last, &other.last);
+        add(&mut self.wins, &other.wins);
+        add(&mut self.draw_index_sum, &other.draw_index_sum);
+    }
+}
+
+/// A probability estimate with its 95% Wilson score interval.
+#[derive(Copy, Clone, Debug)]
+pub struct Probability {
+    pub estimate: f64,
+    pub low: f64,
+    pub high: f64,
+}
+
+impl Probability {
+    fn new(successes: u64, trials: u64) -> Probability {
+        const Z: f64 = 1.96;
+        let n = trials as f64;
+        let p = successes as f64 / n;
+        let denominator = 1.0 + Z * Z / n;
+        let center = (p + Z * Z / (2.0 * n)) / denominator;
+        let margin = Z * (p * (1.0 - p) / n + Z * Z / (4.0 * n * n)).sqrt()
+            / denominator;
+        Probability {
+            estimate: p,
+            low: (center - margin).max(0.0),
+            high: (center + margin).min(1.0),
+        }
+    }
+}
+
+impl std::fmt::Display for Probability {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> Result<(), std::fmt::Error> {
+        write!(
+            f,
+            "{:.4} [{:.4}, {:.4}]",
+            self.estimate, self.low, self.high
+        )
+    }
+}
+
+pub struct BoardEstimate {
+    pub board: usize,
+    pub first: Probability,
+    pub last: Probability,
+    pub win: Probability,
+    /// Mean 0-based index of the winning draw, over the games the board won.
+    pub expected_draw_index: Option<f64>,
+}
+
+fn simulate(
+    game: &Game,
+    drawn_numbers: &[usize],
+    seed: u64,
+    trials: std::ops::Range<usize>,
+) -> Tally {
+    let mut tally = Tally::new(game.boards.len());
+    let mut draws = drawn_numbers.to_vec();
+    for trial in trials {
+        let mut rng = Rng::for_stream(seed, trial as u64);
+        draws.copy_from_slice(drawn_numbers);
+        rng.shuffle(&mut draws);
+
+        // A board only wins first or last if no other board wins on the same
+        // draw, as in `search`.
+        let ranking = game.clone().play(&draws);
+        if let Some(first) = ranking.first() {
+            if ranking.wins.get(1).map(|r| r.draw_index)
+                != Some(first.0.draw_index)
+            {
+                tally.first[first.0.board] += 1;
+            }
+        }
+        if let Some(last) = ranking.last() {
+            let num_wins = ranking.wins.len();
+            if num_wins < 2
+                || ranking.wins[num_wins - 2].draw_index != last.0.draw_index
+            {
+                tally.last[last.0.board] += 1;
+            }
+        }
+        for record in ranking.wins.iter() {
+            tally.wins[record.board] += 1;
+            tally.draw_index_sum[record.board] += record.draw_index as u64;
+        }
+    }
+    tally
+}
+
+/// Estimates how likely each board is to win first and last, without a tie,
+/// by replaying the game with the drawn numbers shuffled. Each trial is
+/// seeded from `seed` and its own index, so results are the same for any
+/// number of threads.
+pub fn estimate(
+    game: &Game,
+    drawn_numbers: &[usize],
+    trials: usize,
+    seed: u64,
+    threads: usize,
+) -> Vec<BoardEstimate> {
+    let threads = threads.clamp(1, trials.max(1));
+    let chunk_size = trials.div_ceil(threads);
+    let tallies: Vec<Tally> = std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..threads)
+            .map(|t| {
+                let start = (t * chunk_size).min(trials);
+                let end = ((t + 1) * chunk_size).min(trials);
+                scope.spawn(move || {
+                    simulate(game, drawn_numbers, seed, start..end)
+                })
+            })
+            .collect();
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    });
+
+    let mut total = Tally::new(game.boards.len());
+    for tally in tallies.iter() {
+        total.merge(tally);
+    }
+
+    let trials = trials as u64;
+    (0..game.boards.len())
+        .map(|board| BoardEstimate {
+            board,
+            first: Probability::new(total.first[board], trials),
+            last: Probability::new(total.last[board], trials),
+            win: Probability::new(total.wins[board], trials),
+            expected_draw_index: (total.wins[board] > 0).then(|| {
+                total.draw_index_sum[board] as f64 / total.wins[board] as f64
+            }),
+        })
+        .collect()
+}
@@ -1,8 +1,19 @@
+mod client;
+mod estimate;
+mod rng;
+mod rules;
+mod search;
+mod server;
+mod snapshot;
+
 use anyhow::{anyhow, bail, Context, Result};
+use rng::Rng;
+use rules::{Win, WinRule};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
-
-const BOARD_SIZE: usize = 5;
+use std::io::{BufRead, BufReader, IsTerminal, Write};
+use std::sync::Arc;
 
 #[derive(Copy, Clone, Debug)]
 struct BoardValue {
@@ -19,163 +30,822 @@ impl BoardValue {
     }
 }
 
-type BoardRow = [BoardValue; BOARD_SIZE];
-type BoardValues = [BoardRow; BOARD_SIZE];
+type BoardRow = Vec<BoardValue>;
+type BoardValues = Vec<BoardRow>;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct BoardSize {
+    rows: usize,
+    cols: usize,
+}
+
+impl std::str::FromStr for BoardSize {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<BoardSize> {
+        let (rows, cols) = s.split_once('x').ok_or_else(|| {
+            anyhow!("Board size '{}' is not of the form NxM.", s)
+        })?;
+        let parse = |n: &str| {
+            n.parse::<usize>().with_context(|| {
+                format!("Failed to parse '{}' as unsigned integer.", n)
+            })
+        };
+        let size = BoardSize {
+            rows: parse(rows)?,
+            cols: parse(cols)?,
+        };
+        if size.rows == 0 || size.cols == 0 {
+            bail!("Board size {} is empty.", s);
+        }
+        Ok(size)
+    }
+}
+
+impl std::fmt::Display for BoardSize {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> Result<(), std::fmt::Error> {
+        write!(f, "{}x{}", self.rows, self.cols)
+    }
+}
 
-#[derive(Copy, Clone)]
+#[derive(Clone)]
 struct Board {
+    size: BoardSize,
     values: BoardValues,
+    // Number of marked cells in each row, column and diagonal, so that win
+    // rules can be checked as cells are marked instead of by scanning every
+    // line.
+    row_marks: Vec<usize>,
+    col_marks: Vec<usize>,
+    diag_marks: usize,
+    anti_diag_marks: usize,
+    marked_count: usize,
+    full_row: Option<usize>,
+    full_col: Option<usize>,
+    unmarked_sum: usize,
+    win: Option<Win>,
 }
 
 impl Board {
-    fn parse(rows: &[&str]) -> Result<Board> {
-        if rows.len() != BOARD_SIZE {
-            bail!("Invalid number of rows {}.", rows.len());
-        }
-
-        let mut board_values: Vec<BoardRow> = Vec::new();
-        for &row in rows.iter() {
-            let row_numbers: Vec<usize> = row
-                .split(' ')
-                .filter(|&s| s != "")
-                .map(|s| {
-                    s.parse().with_context(|| {
-                        format!("Failed to parse '{}' as integer.", s)
-                    })
-                })
-                .collect::<Result<_>>()?;
-            board_values.push(
-                row_numbers
-                    .iter()
-                    .map(|&n| BoardValue::new(n))
+    /// Parses a board from its rows, where `first_line` is the 1-based line
+    /// number of the first row in the input. Every problem found is reported,
+    /// not just the first.
+    fn parse(
+        rows: &[&str],
+        size: BoardSize,
+        first_line: usize,
+    ) -> Result<Board, InputErrors> {
+        let mut errors = Vec::new();
+        if rows.len() != size.rows {
+            errors.push(InputError::new(
+                first_line,
+                format!(
+                    "Board starting on this line has {} rows, expected {}.",
+                    rows.len(),
+                    size.rows
+                ),
+            ));
+        }
+
+        let mut board_values: BoardValues = Vec::new();
+        let mut seen: HashMap<usize, usize> = HashMap::new();
+        for (line, &row) in (first_line..).zip(rows.iter()) {
+            let mut row_values = Vec::new();
+            for s in row.split_whitespace() {
+                match s.parse::<usize>() {
+                    Ok(n) => row_values.push(BoardValue::new(n)),
+                    Err(_) => errors.push(InputError::new(
+                        line,
+                        format!("Failed to parse '{}' as integer.", s),
+                    )),
+                }
+            }
+            if row_values.len() != size.cols {
+                errors.push(InputError::new(
+                    line,
+                    format!(
+                        "Row has {} numbers, expected {}.",
+                        row_values.len(),
+                        size.cols
+                    ),
+                ));
+            }
+            for bv in row_values.iter() {
+                if let Some(previous) = seen.insert(bv.value, line) {
+                    errors.push(InputError::new(
+                        line,
+                        format!(
+                            "{} already appears on line {} of this board.",
+                            bv.value, previous
+                        ),
+                    ));
+                }
+            }
+            board_values.push(row_values);
+        }
+
+        if errors.is_empty() {
+            Ok(Board::new(size, board_values))
+        } else {
+            Err(InputErrors(errors))
+        }
+    }
+
+    /// Generates a board of distinct numbers below `max_number`.
+    fn generate(size: BoardSize, max_number: usize, rng: &mut Rng) -> Board {
+        let mut numbers: Vec<usize> = (0..max_number).collect();
+        rng.shuffle(&mut numbers);
+        let board_values = numbers[..size.rows * size.cols]
+            .chunks(size.cols)
+            .map(|row| row.iter().map(|&n| BoardValue::new(n)).collect())
+            .collect();
+        Board::new(size, board_values)
+    }
+
+    fn new(size: BoardSize, board_values: BoardValues) -> Board {
+        let unmarked_sum = board_values
+            .iter()
+            .map(|row| row.iter().map(|bv| bv.value).sum::<usize>())
+            .sum();
+        Board {
+            size,
+            values: board_values,
+            row_marks: vec![0; size.rows],
+            col_marks: vec![0; size.cols],
+            diag_marks: 0,
+            anti_diag_marks: 0,
+            marked_count: 0,
+            full_row: None,
+            full_col: None,
+            unmarked_sum,
+            win: None,
+        }
+    }
+
+    /// Returns the rows of the board in the format accepted by `parse`.
+    fn to_rows(&self) -> Vec<String> {
+        self.values
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|bv| format!("{:2}", bv.value))
                     .collect::<Vec<_>>()
-                    .try_into()
-                    .unwrap(),
-            );
+                    .join(" ")
+            })
+            .collect()
+    }
+
+    fn mark_at(&mut self, row: usize, col: usize) {
+        let board_value = &mut self.values[row][col];
+        if board_value.marked {
+            return;
+        }
+        board_value.marked = true;
+        self.unmarked_sum -= board_value.value;
+
+        self.marked_count += 1;
+        self.row_marks[row] += 1;
+        self.col_marks[col] += 1;
+        if row == col {
+            self.diag_marks += 1;
         }
+        if row + col + 1 == self.size.cols {
+            self.anti_diag_marks += 1;
+        }
+        if self.full_row.is_none() && self.row_marks[row] == self.size.cols {
+            self.full_row = Some(row);
+        }
+        if self.full_col.is_none() && self.col_marks[col] == self.size.rows {
+            self.full_col = Some(col);
+        }
+    }
 
-        Ok(Board {
-            values: board_values.try_into().unwrap(),
-        })
+    fn complete(&self) -> bool {
+        self.win.is_some()
     }
 
-    fn mark(&mut self, value: usize) {
-        for row in self.values.iter_mut() {
-            for board_value in row.iter_mut() {
-                if board_value.value == value {
-                    board_value.marked = true;
+    fn sum_unmarked(&self) -> usize {
+        self.unmarked_sum
+    }
+}
+
+/// Whether boards should be drawn with ANSI colours rather than plain text.
+fn use_colour() -> bool {
+    std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none()
+}
+
+impl Board {
+    /// Returns a value that formats the board, with ANSI colours if `colour`
+    /// is set.
+    fn display(&self, colour: bool) -> BoardDisplay<'_> {
+        BoardDisplay {
+            board: self,
+            colour,
+        }
+    }
+}
+
+struct BoardDisplay<'a> {
+    board: &'a Board,
+    colour: bool,
+}
+
+impl std::fmt::Display for BoardDisplay<'_> {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> Result<(), std::fmt::Error> {
+        // With colour, marked cells are bold and the winning cells are green.
+        // Without it, marked cells get a '*' suffix.
+        let (board, colour) = (self.board, self.colour);
+        let winning_cells = board
+            .win
+            .as_ref()
+            .map(|win| win.cells(board.size))
+            .unwrap_or_default();
+        for (row, board_row) in board.values.iter().enumerate() {
+            for (col, value) in board_row.iter().enumerate() {
+                if !colour {
+                    write!(
+                        f,
+                        "{}{}\t",
+                        value.value,
+                        if value.marked { "*" } else { "" }
+                    )?;
+                } else if winning_cells.contains(&(row, col)) {
+                    write!(f, "\x1b[1;32m{}\x1b[0m\t", value.value)?;
+                } else if value.marked {
+                    write!(f, "\x1b[1m{}\x1b[0m\t", value.value)?;
+                } else {
+                    write!(f, "{}\t", value.value)?;
                 }
             }
+            writeln!(f)?;
         }
+        Ok(())
     }
+}
 
-    fn complete(&self) -> bool {
-        // Check for completed columns.
-        for i in 0..BOARD_SIZE {
-            if self.values.iter().map(|c| c[i]).all(|bv| bv.marked) {
-                return true;
+#[derive(Copy, Clone, Debug)]
+struct CellRef {
+    board: usize,
+    row: usize,
+    col: usize,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct WinRecord {
+    board: usize,
+    draw_index: usize,
+    number: usize,
+    win: Win,
+    score: usize,
+}
+
+impl std::fmt::Display for WinRecord {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> Result<(), std::fmt::Error> {
+        write!(
+            f,
+            "board {} wins by {} when {} is drawn after {} draws, \
+             with a score of {}",
+            self.board,
+            self.win,
+            self.number,
+            self.draw_index + 1,
+            self.score
+        )
+    }
+}
+
+/// The first board to win, which answers part 1.
+struct FirstWinner<'a>(&'a WinRecord);
+
+/// The last board to win, which answers part 2.
+struct LastWinner<'a>(&'a WinRecord);
+
+/// The order in which the boards won, plus the boards that never did.
+struct Ranking {
+    wins: Vec<WinRecord>,
+    never_won: Vec<usize>,
+}
+
+impl Ranking {
+    fn first(&self) -> Option<FirstWinner<'_>> {
+        self.wins.first().map(FirstWinner)
+    }
+
+    fn last(&self) -> Option<LastWinner<'_>> {
+        self.wins.last().map(LastWinner)
+    }
+}
+
+/// A game in progress. The index and rule never change, so clones share them
+/// and only copy the boards.
+#[derive(Clone)]
+struct Game {
+    boards: Vec<Board>,
+    // Every cell containing a given number, in board order.
+    index: Arc<HashMap<usize, Vec<CellRef>>>,
+    rule: Arc<dyn WinRule>,
+    // Numbers drawn so far, and the boards that won, in order.
+    drawn: Vec<usize>,
+    wins: Vec<WinRecord>,
+}
+
+impl Game {
+    fn new(boards: Vec<Board>, rule: Box<dyn WinRule>) -> Game {
+        let mut index: HashMap<usize, Vec<CellRef>> = HashMap::new();
+        for (i, board) in boards.iter().enumerate() {
+            for (row, board_row) in board.values.iter().enumerate() {
+                for (col, board_value) in board_row.iter().enumerate() {
+                    index.entry(board_value.value).or_default().push(CellRef {
+                        board: i,
+                        row,
+                        col,
+                    });
+                }
             }
         }
+        Game {
+            boards,
+            index: Arc::new(index),
+            rule: rule.into(),
+            drawn: Vec::new(),
+            wins: Vec::new(),
+        }
+    }
 
-        // Check for completed rows.
-        for row in self.values.iter() {
-            if row.iter().all(|bv| bv.marked) {
-                return true;
+    /// Marks the number on every board that has not yet won and returns the
+    /// boards that won as a result, in board order.
+    fn draw(&mut self, number: usize) -> Vec<usize> {
+        let draw_index = self.drawn.len();
+        self.drawn.push(number);
+        let mut winners = Vec::new();
+        for cell in self.index.get(&number).into_iter().flatten() {
+            let board = &mut self.boards[cell.board];
+            if board.complete() {
+                continue;
+            }
+
+            board.mark_at(cell.row, cell.col);
+            board.win = self.rule.check(board);
+            if board.complete() {
+                winners.push(cell.board);
+                self.wins.push(WinRecord {
+                    board: cell.board,
+                    draw_index,
+                    number,
+                    win: board.win.clone().unwrap(),
+                    score: board.sum_unmarked() * number,
+                });
             }
         }
+        winners
+    }
 
-        return false;
+    /// Returns the order in which boards have won so far.
+    fn ranking(&self) -> Ranking {
+        let never_won = (0..self.boards.len())
+            .filter(|&i| !self.boards[i].complete())
+            .collect();
+        Ranking {
+            wins: self.wins.clone(),
+            never_won,
+        }
     }
 
-    fn sum_unmarked(&self) -> usize {
-        self.values
-            .iter()
-            .map(|row| {
-                row.iter()
-                    .filter(|bv| !bv.marked)
-                    .map(|bv| bv.value)
-                    .sum::<usize>()
-            })
-            .sum()
+    /// Plays every drawn number and returns the order in which boards won.
+    fn play(&mut self, drawn_numbers: &[usize]) -> Ranking {
+        for &number in drawn_numbers {
+            self.draw(number);
+        }
+        self.ranking()
     }
 }
 
-impl std::fmt::Display for Board {
+/// Parses the value following a command-line flag.
+fn next_arg<T>(args: &mut impl Iterator<Item = String>, what: &str) -> Result<T>
+where
+    T: std::str::FromStr,
+    T::Err: Into<anyhow::Error>,
+{
+    let arg = args
+        .next()
+        .ok_or_else(|| anyhow!("No {} provided.", what))?;
+    arg.parse::<T>()
+        .map_err(Into::into)
+        .with_context(|| format!("Failed to parse '{}' as {}.", arg, what))
+}
+
+/// A problem found in the puzzle input, with its 1-based line number.
+#[derive(Debug)]
+struct InputError {
+    line: usize,
+    message: String,
+}
+
+impl InputError {
+    fn new(line: usize, message: String) -> InputError {
+        InputError { line, message }
+    }
+}
+
+#[derive(Debug)]
+struct InputErrors(Vec<InputError>);
+
+impl std::fmt::Display for InputErrors {
     fn fmt(
         &self,
         f: &mut std::fmt::Formatter<'_>,
     ) -> Result<(), std::fmt::Error> {
-        for row in self.values {
-            for value in row {
-                write!(
-                    f,
-                    "{}{}\t",
-                    value.value,
-                    if value.marked { "*" } else { "" }
-                )?;
+        for (i, error) in self.0.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
             }
-            write!(f, "\n")?;
+            write!(f, "Line {}: {}", error.line, error.message)?;
         }
         Ok(())
     }
 }
 
-fn main() -> Result<()> {
-    let filename = std::env::args()
-        .nth(1)
-        .ok_or(anyhow!("No filename provided."))?;
+impl std::error::Error for InputErrors {}
+
+/// Reads the drawn numbers and boards from a puzzle input file. Boards are
+/// separated by blank lines. Unless given explicitly, the board size is
+/// inferred from the first board.
+fn load(
+    filename: &str,
+    size: Option<BoardSize>,
+) -> Result<(Vec<usize>, Vec<Board>, BoardSize)> {
     let file = File::open(filename).context("Failed to open file.")?;
     let reader = BufReader::new(file);
 
     let lines: Vec<String> = reader
         .lines()
         .collect::<Result<Vec<String>, _>>()
-        .context("Failed to read line.")?
-        .into_iter()
-        .filter(|s| !s.is_empty())
-        .collect();
-
-    let drawn_numbers: Vec<usize> = lines
-        .iter()
-        .nth(0)
-        .ok_or(anyhow!("Missing drawn numbers."))?
-        .split(",")
-        .map(|s| {
-            s.parse()
-                .with_context(|| format!("Failed to parse {} as integer.", s))
-        })
-        .collect::<Result<_>>()?;
+        .context("Failed to read line.")?;
+
+    let mut errors = Vec::new();
+    let mut drawn_numbers: Vec<usize> = Vec::new();
+    let mut draw_positions: HashMap<usize, usize> = HashMap::new();
+    let draws_line = lines.first().ok_or(anyhow!("Missing drawn numbers."))?;
+    for (position, s) in draws_line.split(',').enumerate() {
+        let Ok(number) = s.trim().parse::<usize>() else {
+            errors.push(InputError::new(
+                1,
+                format!("Failed to parse draw '{}' as integer.", s),
+            ));
+            continue;
+        };
+        if let Some(previous) = draw_positions.insert(number, position) {
+            errors.push(InputError::new(
+                1,
+                format!(
+                    "{} is drawn twice, at positions {} and {}.",
+                    number, previous, position
+                ),
+            ));
+        }
+        drawn_numbers.push(number);
+    }
+
+    // Group the remaining lines into boards, remembering where each starts.
+    let mut chunks: Vec<(usize, Vec<&str>)> = Vec::new();
+    let mut in_board = false;
+    for (line, s) in (1..).zip(lines.iter()).skip(1) {
+        let s = s.trim_end();
+        if s.is_empty() {
+            in_board = false;
+        } else if in_board {
+            chunks.last_mut().unwrap().1.push(s);
+        } else {
+            chunks.push((line, vec![s]));
+            in_board = true;
+        }
+    }
+
+    let size = match size {
+        Some(size) => size,
+        None => {
+            let (_, first) =
+                chunks.first().ok_or(anyhow!("Missing bingo boards."))?;
+            BoardSize {
+                rows: first.len(),
+                cols: first[0].split_whitespace().count(),
+            }
+        }
+    };
 
     let mut boards = Vec::new();
-    for chunk in lines
-        .iter()
-        .skip(1)
-        .map(|s| s.as_str())
-        .collect::<Vec<_>>()
-        .chunks(BOARD_SIZE)
-    {
-        boards
-            .push(Board::parse(chunk).context("Failed to parse bingo board.")?);
-    }
-
-    for number in drawn_numbers {
-        for board in boards.iter_mut() {
-            if board.complete() {
-                continue;
+    for (first_line, rows) in chunks.iter() {
+        match Board::parse(rows, size, *first_line) {
+            Ok(board) => boards.push(board),
+            Err(InputErrors(board_errors)) => errors.extend(board_errors),
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(InputErrors(errors))
+            .with_context(|| format!("Invalid {} bingo input.", size));
+    }
+    Ok((drawn_numbers, boards, size))
+}
+
+#[derive(Default)]
+struct PlayOptions {
+    /// Stop once this many numbers have been drawn in total.
+    stop_after: Option<usize>,
+    /// Write the state of the game here when play stops.
+    save: Option<String>,
+    /// Wait for Enter before each draw and show the boards it changed.
+    step: bool,
+    /// Draw boards with ANSI colours.
+    colour: bool,
+}
+
+fn play(
+    mut game: Game,
+    drawn_numbers: &[usize],
+    rule: &str,
+    options: &PlayOptions,
+) -> Result<()> {
+    let stdin = std::io::stdin();
+    let mut next = 0;
+    while next < drawn_numbers.len() {
+        if options.stop_after.is_some_and(|k| game.drawn.len() >= k) {
+            break;
+        }
+        if options.step {
+            print!("Press Enter to draw the next number, or q to stop: ");
+            std::io::stdout().flush()?;
+            let mut input = String::new();
+            if stdin.read_line(&mut input)? == 0 || input.trim() == "q" {
+                break;
             }
+        }
 
-            board.mark(number);
-            if board.complete() {
+        let number = drawn_numbers[next];
+        next += 1;
+        let marked_before: Vec<usize> =
+            game.boards.iter().map(|b| b.marked_count).collect();
+        game.draw(number);
+        if options.step {
+            println!("Draw {}: {}", game.drawn.len(), number);
+            for (i, board) in game.boards.iter().enumerate() {
+                if board.marked_count == marked_before[i] {
+                    continue;
+                }
+                // Boards stop being marked once they win, so a changed board
+                // that has won did so on this draw.
+                match board.win.as_ref() {
+                    Some(win) => println!("Board {} wins by {}:", i, win),
+                    None => println!("Board {}:", i),
+                }
+                println!("{}", board.display(options.colour));
+            }
+        }
+    }
+    let remaining = &drawn_numbers[next..];
+
+    if let Some(path) = options.save.as_ref() {
+        snapshot::save(path, &game, rule, remaining)?;
+        println!(
+            "Saved the game after {} draws to {}.",
+            game.drawn.len(),
+            path
+        );
+    }
+
+    let ranking = game.ranking();
+    for (place, record) in ranking.wins.iter().enumerate() {
+        println!("{}. {}", place + 1, record);
+    }
+    if !remaining.is_empty() {
+        println!(
+            "Stopped after {} draws with {} remaining.",
+            game.drawn.len(),
+            remaining.len()
+        );
+        return Ok(());
+    }
+    if !ranking.never_won.is_empty() {
+        println!(
+            "Boards that never win: {}",
+            ranking
+                .never_won
+                .iter()
+                .map(|i| i.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+    println!();
+
+    match ranking.first() {
+        Some(FirstWinner(record)) => {
+            println!("First to win: {}:", record);
+            println!("{}", game.boards[record.board].display(options.colour));
+        }
+        None => println!("No board wins."),
+    }
+    if let Some(LastWinner(record)) = ranking.last() {
+        println!("Last to win: {}:", record);
+        println!("{}", game.boards[record.board].display(options.colour));
+    }
+
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let mut args = std::env::args().skip(1).peekable();
+    let command = match args.peek().map(|s| s.as_str()) {
+        Some("serve") | Some("client") => args.next(),
+        _ => None,
+    };
+
+    let mut positional = None;
+    let mut size = None;
+    let mut rule = "lines".to_string();
+    let mut trials = None;
+    let mut seed = 0;
+    let mut threads = std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1);
+    let mut target = None;
+    let mut search_limit = 1_000_000;
+    let mut server_options = server::Options::default();
+    let mut play_options = PlayOptions::default();
+    let mut resume: Option<String> = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--size" => size = Some(next_arg(&mut args, "board size")?),
+            "--rule" => rule = next_arg(&mut args, "win rule")?,
+            "--estimate" => {
+                trials = Some(next_arg(&mut args, "number of trials")?)
+            }
+            "--seed" => seed = next_arg(&mut args, "seed")?,
+            "--threads" => threads = next_arg(&mut args, "number of threads")?,
+            "--target-first" => {
+                target = Some((
+                    next_arg(&mut args, "target board")?,
+                    search::Goal::First,
+                ))
+            }
+            "--target-last" => {
+                target = Some((
+                    next_arg(&mut args, "target board")?,
+                    search::Goal::Last,
+                ))
+            }
+            "--search-limit" => {
+                search_limit = next_arg(&mut args, "search limit")?
+            }
+            "--port" => server_options.port = next_arg(&mut args, "port")?,
+            "--players" => {
+                server_options.players =
+                    next_arg(&mut args, "number of players")?
+            }
+            "--interval-ms" => {
+                server_options.interval = std::time::Duration::from_millis(
+                    next_arg(&mut args, "draw interval")?,
+                )
+            }
+            "--generate" => {
+                server_options.generate =
+                    Some(next_arg(&mut args, "board size")?)
+            }
+            "--stop-after" => {
+                play_options.stop_after =
+                    Some(next_arg(&mut args, "number of draws")?)
+            }
+            "--save" => {
+                play_options.save = Some(next_arg(&mut args, "save path")?)
+            }
+            "--resume" => resume = Some(next_arg(&mut args, "resume path")?),
+            "--step" => play_options.step = true,
+            _ if arg.starts_with("--") => {
+                bail!("Unrecognized option '{}'.", arg)
+            }
+            _ if positional.is_some() => {
+                bail!("Unexpected argument '{}'.", arg)
+            }
+            _ => positional = Some(arg),
+        }
+    }
+
+    let colour = use_colour();
+    play_options.colour = colour;
+    server_options.colour = colour;
+
+    if command.as_deref() == Some("client") {
+        let address =
+            positional.ok_or(anyhow!("No server address provided."))?;
+        return client::run(&address, colour);
+    }
+
+    if let Some(path) = resume {
+        let (game, remaining, rule) = snapshot::load(&path)?;
+        return play(game, &remaining, &rule, &play_options);
+    }
+
+    let (drawn_numbers, boards, size) =
+        match (positional, server_options.generate) {
+            (Some(filename), _) => load(&filename, size)?,
+            (None, Some(size)) if command.is_some() => {
+                let mut rng = Rng::new(seed);
+                let max_number = 4 * size.rows * size.cols;
+                let boards = (0..server_options.players)
+                    .map(|_| Board::generate(size, max_number, &mut rng))
+                    .collect();
+                let mut drawn_numbers: Vec<usize> = (0..max_number).collect();
+                rng.shuffle(&mut drawn_numbers);
+                (drawn_numbers, boards, size)
+            }
+            (None, _) => bail!("No filename provided."),
+        };
+
+    let win_rule = rules::parse(&rule, size)?;
+    if command.is_some() {
+        return server::serve(
+            boards,
+            win_rule,
+            &rule,
+            &drawn_numbers,
+            &server_options,
+        );
+    }
+
+    let game = Game::new(boards, win_rule);
+
+    if let Some((board, goal)) = target {
+        match search::search(&game, &drawn_numbers, board, goal, search_limit)?
+        {
+            search::Outcome::Found(result) => {
                 println!(
-                    "The following board wins when {} is drawn, \
-                     with a score of {}:",
-                    number,
-                    board.sum_unmarked() * number
+                    "Board {} wins {} after {} draws{}:",
+                    board,
+                    goal,
+                    result.win_draws,
+                    if result.proven_minimal {
+                        ""
+                    } else {
+                        " (search limit reached, may not be minimal)"
+                    }
+                );
+                println!(
+                    "{}",
+                    result
+                        .draws
+                        .iter()
+                        .map(|n| n.to_string())
+                        .collect::<Vec<_>>()
+                        .join(",")
+                );
+            }
+            search::Outcome::Impossible => {
+                println!("Board {} cannot win {}.", board, goal);
+            }
+            search::Outcome::Inconclusive => {
+                println!(
+                    "No sequence making board {} win {} was found within \
+                     the search limit.",
+                    board, goal
                 );
-                println!("{}", board);
             }
         }
+        return Ok(());
     }
 
-    Ok(())
+    if let Some(trials) = trials {
+        if trials == 0 {
+            bail!("At least one trial is required.");
+        }
+        println!(
+            "board\tP(first, no tie) [95% CI]\tP(last, no tie) [95% CI]\t\
+             P(win) [95% CI]\tE[winning draw index]"
+        );
+        for board_estimate in
+            estimate::estimate(&game, &drawn_numbers, trials, seed, threads)
+        {
+            println!(
+                "{}\t{}\t{}\t{}\t{}",
+                board_estimate.board,
+                board_estimate.first,
+                board_estimate.last,
+                board_estimate.win,
+                board_estimate
+                    .expected_draw_index
+                    .map_or("-".to_string(), |d| format!("{:.2}", d))
+            );
+        }
+        return Ok(());
+    }
+
+    play(game, &drawn_numbers, &rule, &play_options)
 }
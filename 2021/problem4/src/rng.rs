@@ -0,0 +1,46 @@
+/// A small seeded SplitMix64 generator, so that simulations are reproducible
+/// without pulling in an external crate.
+#[derive(Clone, Debug)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng { state: seed }
+    }
+
+    /// Derives an independent generator for the given stream, e.g. one per
+    /// simulation trial, so that results do not depend on how trials are
+    /// split across threads.
+    pub fn for_stream(seed: u64, stream: u64) -> Rng {
+        Rng::new(Rng::new(seed ^ Rng::new(stream).next_u64()).next_u64())
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a uniformly distributed value in `0..bound`.
+    pub fn below(&mut self, bound: usize) -> usize {
+        // Rejection sampling avoids modulo bias.
+        let bound = bound as u64;
+        let zone = u64::MAX - u64::MAX % bound;
+        loop {
+            let value = self.next_u64();
+            if value < zone {
+                return (value % bound) as usize;
+            }
+        }
+    }
+
+    pub fn shuffle<T>(&mut self, values: &mut [T]) {
+        for i in (1..values.len()).rev() {
+            values.swap(i, self.below(i + 1));
+        }
+    }
+}
@@ -0,0 +1,375 @@
+use crate::{Board, BoardSize};
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+/// The way in which a board satisfied a win rule.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Win {
+    Row(usize),
+    Column(usize),
+    Diagonal,
+    AntiDiagonal,
+    FourCorners,
+    Blackout,
+    Pattern {
+        name: String,
+        cells: Vec<(usize, usize)>,
+    },
+    All(Vec<Win>),
+}
+
+impl Win {
+    /// Returns the cells that make up this win.
+    pub fn cells(&self, size: BoardSize) -> Vec<(usize, usize)> {
+        match self {
+            Win::Row(row) => (0..size.cols).map(|col| (*row, col)).collect(),
+            Win::Column(col) => (0..size.rows).map(|row| (row, *col)).collect(),
+            Win::Diagonal => (0..size.rows).map(|i| (i, i)).collect(),
+            Win::AntiDiagonal => {
+                (0..size.rows).map(|i| (i, size.cols - 1 - i)).collect()
+            }
+            Win::FourCorners => corners(size),
+            Win::Blackout => Blackout.winning_sets(size).remove(0),
+            Win::Pattern { cells, .. } => cells.clone(),
+            Win::All(wins) => {
+                let mut cells: Vec<(usize, usize)> =
+                    wins.iter().flat_map(|win| win.cells(size)).collect();
+                cells.sort_unstable();
+                cells.dedup();
+                cells
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Win {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> Result<(), std::fmt::Error> {
+        match self {
+            Win::Row(row) => write!(f, "row {}", row),
+            Win::Column(col) => write!(f, "column {}", col),
+            Win::Diagonal => write!(f, "diagonal"),
+            Win::AntiDiagonal => write!(f, "anti-diagonal"),
+            Win::FourCorners => write!(f, "four corners"),
+            Win::Blackout => write!(f, "blackout"),
+            Win::Pattern { name, .. } => write!(f, "pattern {}", name),
+            Win::All(wins) => {
+                for (i, win) in wins.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " and ")?;
+                    }
+                    write!(f, "{}", win)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+pub trait WinRule: Send + Sync {
+    /// Returns how the board satisfies this rule, or `None` if it does not.
+    fn check(&self, board: &Board) -> Option<Win>;
+
+    /// Returns sets of cells that satisfy this rule once fully marked. Every
+    /// marking that satisfies the rule contains at least one of them.
+    fn winning_sets(&self, size: BoardSize) -> Vec<Vec<(usize, usize)>>;
+}
+
+/// Any full row or column.
+pub struct Lines {
+    pub rows: bool,
+    pub columns: bool,
+}
+
+impl WinRule for Lines {
+    fn check(&self, board: &Board) -> Option<Win> {
+        if self.rows {
+            if let Some(row) = board.full_row {
+                return Some(Win::Row(row));
+            }
+        }
+        if self.columns {
+            if let Some(col) = board.full_col {
+                return Some(Win::Column(col));
+            }
+        }
+        None
+    }
+
+    fn winning_sets(&self, size: BoardSize) -> Vec<Vec<(usize, usize)>> {
+        let mut sets = Vec::new();
+        if self.rows {
+            sets.extend(
+                (0..size.rows)
+                    .map(|row| (0..size.cols).map(|col| (row, col)).collect()),
+            );
+        }
+        if self.columns {
+            sets.extend(
+                (0..size.cols)
+                    .map(|col| (0..size.rows).map(|row| (row, col)).collect()),
+            );
+        }
+        sets
+    }
+}
+
+/// Either of the two diagonals of a square board.
+pub struct Diagonals;
+
+impl WinRule for Diagonals {
+    fn check(&self, board: &Board) -> Option<Win> {
+        if board.diag_marks == board.size.rows {
+            Some(Win::Diagonal)
+        } else if board.anti_diag_marks == board.size.rows {
+            Some(Win::AntiDiagonal)
+        } else {
+            None
+        }
+    }
+
+    fn winning_sets(&self, size: BoardSize) -> Vec<Vec<(usize, usize)>> {
+        let n = size.rows;
+        vec![
+            (0..n).map(|i| (i, i)).collect(),
+            (0..n).map(|i| (i, n - 1 - i)).collect(),
+        ]
+    }
+}
+
+fn corners(size: BoardSize) -> Vec<(usize, usize)> {
+    let last_row = size.rows - 1;
+    let last_col = size.cols - 1;
+    let mut cells =
+        vec![(0, 0), (0, last_col), (last_row, 0), (last_row, last_col)];
+    cells.sort_unstable();
+    cells.dedup();
+    cells
+}
+
+pub struct FourCorners;
+
+impl WinRule for FourCorners {
+    fn check(&self, board: &Board) -> Option<Win> {
+        corners(board.size)
+            .iter()
+            .all(|&(row, col)| board.values[row][col].marked)
+            .then_some(Win::FourCorners)
+    }
+
+    fn winning_sets(&self, size: BoardSize) -> Vec<Vec<(usize, usize)>> {
+        vec![corners(size)]
+    }
+}
+
+/// Every cell on the board.
+pub struct Blackout;
+
+impl WinRule for Blackout {
+    fn check(&self, board: &Board) -> Option<Win> {
+        (board.marked_count == board.size.rows * board.size.cols)
+            .then_some(Win::Blackout)
+    }
+
+    fn winning_sets(&self, size: BoardSize) -> Vec<Vec<(usize, usize)>> {
+        vec![(0..size.rows)
+            .flat_map(|row| (0..size.cols).map(move |col| (row, col)))
+            .collect()]
+    }
+}
+
+/// Every cell set in a user-supplied mask.
+pub struct Pattern {
+    name: String,
+    cells: Vec<(usize, usize)>,
+}
+
+impl Pattern {
+    /// Parses a pattern written as rows of 0s and 1s separated by '/', e.g.
+    /// "101/010/101".
+    fn parse_rows(s: &str, size: BoardSize) -> Result<Pattern> {
+        let rows: Vec<&str> = s.split('/').collect();
+        if rows.len() != size.rows {
+            bail!(
+                "Pattern '{}' has {} rows, expected {}.",
+                s,
+                rows.len(),
+                size.rows
+            );
+        }
+        let mut cells = Vec::new();
+        for (row, row_str) in rows.iter().enumerate() {
+            if row_str.len() != size.cols {
+                bail!(
+                    "Pattern row '{}' has {} columns, expected {}.",
+                    row_str,
+                    row_str.len(),
+                    size.cols
+                );
+            }
+            for (col, c) in row_str.chars().enumerate() {
+                match c {
+                    '1' => cells.push((row, col)),
+                    '0' => (),
+                    _ => bail!("Pattern row '{}' is not binary.", row_str),
+                }
+            }
+        }
+        Pattern::new(s.to_string(), cells)
+    }
+
+    /// Parses a pattern written as an integer bitmask, where bit 0 is the
+    /// top-left cell and bits proceed in row-major order.
+    fn parse_mask(s: &str, size: BoardSize) -> Result<Pattern> {
+        let mask = if let Some(hex) = s.strip_prefix("0x") {
+            u128::from_str_radix(hex, 16)
+        } else if let Some(binary) = s.strip_prefix("0b") {
+            u128::from_str_radix(binary, 2)
+        } else {
+            s.parse()
+        }
+        .with_context(|| format!("Failed to parse '{}' as a bitmask.", s))?;
+
+        let num_cells = size.rows * size.cols;
+        if num_cells < 128 && mask >> num_cells != 0 {
+            bail!("Mask {} has bits beyond the {} board.", s, size);
+        }
+        let cells = (0..num_cells.min(128))
+            .filter(|&bit| mask & (1 << bit) != 0)
+            .map(|bit| (bit / size.cols, bit % size.cols))
+            .collect();
+        Pattern::new(s.to_string(), cells)
+    }
+
+    fn new(name: String, cells: Vec<(usize, usize)>) -> Result<Pattern> {
+        if cells.is_empty() {
+            bail!("Pattern '{}' is empty.", name);
+        }
+        Ok(Pattern { name, cells })
+    }
+}
+
+impl WinRule for Pattern {
+    fn check(&self, board: &Board) -> Option<Win> {
+        self.cells
+            .iter()
+            .all(|&(row, col)| board.values[row][col].marked)
+            .then(|| Win::Pattern {
+                name: self.name.clone(),
+                cells: self.cells.clone(),
+            })
+    }
+
+    fn winning_sets(&self, _size: BoardSize) -> Vec<Vec<(usize, usize)>> {
+        vec![self.cells.clone()]
+    }
+}
+
+/// Satisfied when any of the inner rules is.
+pub struct AnyOf(Vec<Box<dyn WinRule>>);
+
+impl WinRule for AnyOf {
+    fn check(&self, board: &Board) -> Option<Win> {
+        self.0.iter().find_map(|rule| rule.check(board))
+    }
+
+    fn winning_sets(&self, size: BoardSize) -> Vec<Vec<(usize, usize)>> {
+        self.0
+            .iter()
+            .flat_map(|rule| rule.winning_sets(size))
+            .collect()
+    }
+}
+
+/// Satisfied when all of the inner rules are.
+pub struct AllOf(Vec<Box<dyn WinRule>>);
+
+impl WinRule for AllOf {
+    fn check(&self, board: &Board) -> Option<Win> {
+        self.0
+            .iter()
+            .map(|rule| rule.check(board))
+            .collect::<Option<Vec<_>>>()
+            .map(Win::All)
+    }
+
+    fn winning_sets(&self, size: BoardSize) -> Vec<Vec<(usize, usize)>> {
+        // Every combination of one set from each inner rule.
+        let mut sets = vec![Vec::new()];
+        for rule in self.0.iter() {
+            let rule_sets = rule.winning_sets(size);
+            sets = sets
+                .iter()
+                .flat_map(|set| {
+                    rule_sets.iter().map(move |rule_set| {
+                        let mut union: Vec<(usize, usize)> =
+                            set.iter().chain(rule_set).copied().collect();
+                        union.sort_unstable();
+                        union.dedup();
+                        union
+                    })
+                })
+                .collect();
+        }
+        sets
+    }
+}
+
+fn parse_single(s: &str, size: BoardSize) -> Result<Box<dyn WinRule>> {
+    Ok(match s {
+        "lines" => Box::new(Lines {
+            rows: true,
+            columns: true,
+        }),
+        "rows" => Box::new(Lines {
+            rows: true,
+            columns: false,
+        }),
+        "columns" => Box::new(Lines {
+            rows: false,
+            columns: true,
+        }),
+        "diagonals" => {
+            if size.rows != size.cols {
+                bail!("Diagonals require a square board, not {}.", size);
+            }
+            Box::new(Diagonals)
+        }
+        "corners" => Box::new(FourCorners),
+        "blackout" => Box::new(Blackout),
+        _ => {
+            if let Some(rows) = s.strip_prefix("pattern:") {
+                Box::new(Pattern::parse_rows(rows, size)?)
+            } else if let Some(mask) = s.strip_prefix("mask:") {
+                Box::new(Pattern::parse_mask(mask, size)?)
+            } else {
+                bail!("Unrecognized win rule '{}'.", s)
+            }
+        }
+    })
+}
+
+/// Parses a rule expression such as "lines|corners&diagonals". '&' binds more
+/// tightly than '|'.
+pub fn parse(expr: &str, size: BoardSize) -> Result<Box<dyn WinRule>> {
+    let mut any_of = Vec::new();
+    for term in expr.split('|') {
+        let mut all_of = term
+            .split('&')
+            .map(|s| parse_single(s.trim(), size))
+            .collect::<Result<Vec<_>>>()
+            .with_context(|| format!("Failed to parse win rule '{}'.", expr))?;
+        any_of.push(if all_of.len() == 1 {
+            all_of.pop().unwrap()
+        } else {
+            Box::new(AllOf(all_of))
+        });
+    }
+    if any_of.len() == 1 {
+        any_of.pop().ok_or(anyhow!("Empty win rule."))
+    } else {
+        Ok(Box::new(AnyOf(any_of)))
+    }
+}
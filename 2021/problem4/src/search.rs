@@ -0,0 +1,351 @@
+use crate::Game;
+use anyhow::{bail, Result};
+use std::collections::HashMap;
+
+/// A set of distinct numbers, stored as bits indexed by each number's
+/// position in the list of available numbers.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct NumberSet(Vec<u64>);
+
+impl NumberSet {
+    fn new(capacity: usize) -> NumberSet {
+        NumberSet(vec![0; capacity.div_ceil(64)])
+    }
+
+    fn insert(&mut self, i: usize) {
+        self.0[i / 64] |= 1 << (i % 64);
+    }
+
+    fn remove(&mut self, i: usize) {
+        self.0[i / 64] &= !(1 << (i % 64));
+    }
+
+    fn contains(&self, i: usize) -> bool {
+        self.0[i / 64] & (1 << (i % 64)) != 0
+    }
+
+    fn len(&self) -> usize {
+        self.0.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    fn is_subset(&self, other: &NumberSet) -> bool {
+        self.0.iter().zip(other.0.iter()).all(|(a, b)| a & !b == 0)
+    }
+
+    fn union(&self, other: &NumberSet) -> NumberSet {
+        NumberSet(
+            self.0
+                .iter()
+                .zip(other.0.iter())
+                .map(|(a, b)| a | b)
+                .collect(),
+        )
+    }
+
+    /// Number of elements in `self` that are not in `other`.
+    fn difference_len(&self, other: &NumberSet) -> usize {
+        self.0
+            .iter()
+            .zip(other.0.iter())
+            .map(|(a, b)| (a & !b).count_ones() as usize)
+            .sum()
+    }
+
+    fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.0.len() * 64).filter(|&i| self.contains(i))
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Goal {
+    First,
+    Last,
+}
+
+impl std::fmt::Display for Goal {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> Result<(), std::fmt::Error> {
+        match self {
+            Goal::First => write!(f, "first"),
+            Goal::Last => write!(f, "last"),
+        }
+    }
+}
+
+pub struct SearchResult {
+    /// A permutation of the drawn numbers.
+    pub draws: Vec<usize>,
+    /// Number of draws until the target board wins.
+    pub win_draws: usize,
+    /// False if the search hit its node limit, in which case a shorter
+    /// sequence may exist.
+    pub proven_minimal: bool,
+}
+
+pub enum Outcome {
+    Found(SearchResult),
+    Impossible,
+    Inconclusive,
+}
+
+/// Candidate sets of numbers for each board, restricted to those that can be
+/// completed from the available numbers. Sets that contain another set of the
+/// same board are dropped.
+fn board_number_sets(
+    game: &Game,
+    ids: &HashMap<usize, usize>,
+) -> Vec<Vec<NumberSet>> {
+    game.boards
+        .iter()
+        .map(|board| {
+            let mut sets: Vec<NumberSet> = Vec::new();
+            'sets: for cells in game.rule.winning_sets(board.size) {
+                let mut set = NumberSet::new(ids.len());
+                for (row, col) in cells {
+                    match ids.get(&board.values[row][col].value) {
+                        Some(&id) => set.insert(id),
+                        None => continue 'sets,
+                    }
+                }
+                sets.push(set);
+            }
+            sets.sort_by_key(|s| s.len());
+            let mut minimal: Vec<NumberSet> = Vec::new();
+            for set in sets {
+                if !minimal.iter().any(|m| m.is_subset(&set)) {
+                    minimal.push(set);
+                }
+            }
+            minimal
+        })
+        .collect()
+}
+
+struct LastSearch<'a> {
+    target_sets: &'a [NumberSet],
+    others: Vec<&'a [NumberSet]>,
+    // The target's final number, which no other board may need.
+    excluded: usize,
+    // Only follow the cheapest option at each step.
+    greedy: bool,
+    nodes: usize,
+    node_limit: usize,
+    truncated: bool,
+    best: Option<(NumberSet, usize)>,
+    best_len: usize,
+}
+
+impl LastSearch<'_> {
+    /// Extends `drawn` until every other board has won, without letting the
+    /// target win, keeping the smallest such set found.
+    fn search(&mut self, drawn: NumberSet) {
+        if !self.greedy {
+            self.nodes += 1;
+            if self.nodes > self.node_limit {
+                self.truncated = true;
+                return;
+            }
+        }
+        if self.target_sets.iter().any(|t| t.is_subset(&drawn)) {
+            return;
+        }
+
+        // Branch on the unsatisfied board with the fewest options, and bound
+        // by the board that needs the most additional numbers.
+        let mut branch: Option<Vec<&NumberSet>> = None;
+        let mut needed = 0;
+        for sets in self.others.iter() {
+            if sets.iter().any(|s| s.is_subset(&drawn)) {
+                continue;
+            }
+            let options: Vec<&NumberSet> =
+                sets.iter().filter(|s| !s.contains(self.excluded)).collect();
+            let Some(cheapest) =
+                options.iter().map(|s| s.difference_len(&drawn)).min()
+            else {
+                return;
+            };
+            needed = needed.max(cheapest);
+            if branch.as_ref().is_none_or(|b| options.len() < b.len()) {
+                branch = Some(options);
+            }
+        }
+        if drawn.len() + needed + 1 >= self.best_len {
+            return;
+        }
+
+        match branch {
+            None => {
+                self.best_len = drawn.len() + 1;
+                self.best = Some((drawn, self.excluded));
+            }
+            Some(mut options) => {
+                options.sort_by_key(|s| s.difference_len(&drawn));
+                if self.greedy {
+                    options.truncate(1);
+                }
+                for option in options {
+                    self.search(drawn.union(option));
+                    if self.truncated {
+                        return;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Searches for an ordering of the drawn numbers in which the target board
+/// wins first (or last) after as few draws as possible. Winning first means no
+/// other board wins on or before the same draw; winning last means every other
+/// board that can win has done so on an earlier draw.
+pub fn search(
+    game: &Game,
+    drawn_numbers: &[usize],
+    target: usize,
+    goal: Goal,
+    node_limit: usize,
+) -> Result<Outcome> {
+    if target >= game.boards.len() {
+        bail!(
+            "Board {} does not exist, there are {} boards.",
+            target,
+            game.boards.len()
+        );
+    }
+
+    let mut available: Vec<usize> = Vec::new();
+    let mut ids: HashMap<usize, usize> = HashMap::new();
+    for &number in drawn_numbers {
+        ids.entry(number).or_insert_with(|| {
+            available.push(number);
+            available.len() - 1
+        });
+    }
+
+    let sets = board_number_sets(game, &ids);
+    let target_sets = &sets[target];
+    let others: Vec<&[NumberSet]> = sets
+        .iter()
+        .enumerate()
+        .filter(|&(i, s)| i != target && !s.is_empty())
+        .map(|(_, s)| s.as_slice())
+        .collect();
+
+    let (drawn, last, proven_minimal) = match goal {
+        Goal::First => {
+            // Any superset of a set that lets another board win also does, so
+            // the smallest target set that no other board is contained in is
+            // optimal.
+            let found = target_sets.iter().find(|t| {
+                !others.iter().flat_map(|s| s.iter()).any(|o| o.is_subset(t))
+            });
+            match found {
+                Some(set) => (set.clone(), None, true),
+                None => return Ok(Outcome::Impossible),
+            }
+        }
+        Goal::Last => {
+            let mut last_search = LastSearch {
+                target_sets,
+                others,
+                excluded: 0,
+                greedy: false,
+                nodes: 0,
+                node_limit,
+                truncated: false,
+                best: None,
+                best_len: usize::MAX,
+            };
+            // The target's final draw can be any number of any of its sets. A
+            // greedy pass over all of them gives a bound for the exhaustive
+            // pass.
+            let starts: Vec<(&NumberSet, usize)> = target_sets
+                .iter()
+                .flat_map(|t| t.iter().map(move |excluded| (t, excluded)))
+                .collect();
+            for greedy in [true, false] {
+                last_search.greedy = greedy;
+                for &(target_set, excluded) in starts.iter() {
+                    if last_search.truncated {
+                        break;
+                    }
+                    if target_set.len() >= last_search.best_len {
+                        continue;
+                    }
+                    let mut drawn = target_set.clone();
+                    drawn.remove(excluded);
+                    last_search.excluded = excluded;
+                    last_search.search(drawn);
+                }
+            }
+            match last_search.best {
+                Some((drawn, excluded)) => {
+                    (drawn, Some(excluded), !last_search.truncated)
+                }
+                None if last_search.truncated => {
+                    return Ok(Outcome::Inconclusive)
+                }
+                None => return Ok(Outcome::Impossible),
+            }
+        }
+    };
+
+    // Draw the chosen numbers first, then the target's final number, then the
+    // rest in their original order.
+    let mut draws: Vec<usize> = drawn.iter().map(|id| available[id]).collect();
+    draws.extend(last.map(|id| available[id]));
+    let mut remaining: HashMap<usize, usize> = HashMap::new();
+    for &number in drawn_numbers {
+        *remaining.entry(number).or_default() += 1;
+    }
+    for number in draws.iter() {
+        *remaining.get_mut(number).unwrap() -= 1;
+    }
+    for &number in drawn_numbers {
+        let count = remaining.get_mut(&number).unwrap();
+        if *count > 0 {
+            *count -= 1;
+            draws.push(number);
+        }
+    }
+
+    // Verify the sequence by replaying it.
+    let ranking = game.clone().play(&draws);
+    let (record, others_ok) = match goal {
+        Goal::First => {
+            let record = ranking.first().map(|f| f.0);
+            let others_ok = record.is_some_and(|r| {
+                ranking.wins[1..]
+                    .iter()
+                    .all(|w| w.draw_index > r.draw_index)
+            });
+            (record, others_ok)
+        }
+        Goal::Last => {
+            let record = ranking.last().map(|l| l.0);
+            let others_ok = record.is_some_and(|r| {
+                ranking.wins[..ranking.wins.len() - 1]
+                    .iter()
+                    .all(|w| w.draw_index < r.draw_index)
+            });
+            (record, others_ok)
+        }
+    };
+    match record {
+        Some(record) if record.board == target && others_ok => {
+            Ok(Outcome::Found(SearchResult {
+                draws,
+                win_draws: record.draw_index + 1,
+                proven_minimal,
+            }))
+        }
+        _ => bail!(
+            "Replaying the draw sequence did not make board {} win {}.",
+            target,
+            goal
+        ),
+    }
+}
@@ -0,0 +1,164 @@
+//! A bingo caller for local multiplayer games. The protocol is line-based
+//! text. After connecting, each client is sent
+//!
+//! ```text
+//! WELCOME <player>
+//! RULE <win rule>
+//! BOARD <rows> <cols>
+//! <one line per row, in the puzzle input format>
+//! ```
+//!
+//! followed by `DRAW <number>` for each drawn number. A client claims a win
+//! by sending `BINGO`. Invalid claims are answered with `REJECTED`. A valid
+//! claim ends the game with `WINNER <player> <score>` sent to every client,
+//! and running out of numbers ends it with `GAMEOVER`.
+
+use crate::rules::WinRule;
+use crate::{Board, BoardSize, Game};
+use anyhow::{anyhow, bail, Context, Result};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+pub struct Options {
+    pub port: u16,
+    pub players: usize,
+    /// How long to wait for claims after each draw.
+    pub interval: Duration,
+    /// Generate random boards of this size instead of loading them.
+    pub generate: Option<BoardSize>,
+    /// Draw the winning board with ANSI colours.
+    pub colour: bool,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            port: 7878,
+            players: 1,
+            interval: Duration::from_millis(500),
+            generate: None,
+            colour: false,
+        }
+    }
+}
+
+fn broadcast(streams: &mut [TcpStream], message: &str) {
+    for stream in streams.iter_mut() {
+        // A player that has disconnected just misses the message.
+        let _ = writeln!(stream, "{}", message);
+    }
+}
+
+pub fn serve(
+    mut boards: Vec<Board>,
+    rule: Box<dyn WinRule>,
+    rule_name: &str,
+    drawn_numbers: &[usize],
+    options: &Options,
+) -> Result<()> {
+    if options.players == 0 {
+        bail!("At least one player is required.");
+    }
+    if options.players > boards.len() {
+        bail!(
+            "There are only {} boards for {} players.",
+            boards.len(),
+            options.players
+        );
+    }
+    boards.truncate(options.players);
+
+    let listener = TcpListener::bind(("127.0.0.1", options.port))
+        .with_context(|| {
+            format!("Failed to listen on port {}.", options.port)
+        })?;
+    println!(
+        "Listening on {}, waiting for {} players.",
+        listener.local_addr()?,
+        options.players
+    );
+
+    let (sender, receiver) = mpsc::channel();
+    let mut streams = Vec::new();
+    for (player, board) in boards.iter().enumerate() {
+        let (stream, address) =
+            listener.accept().context("Failed to accept connection.")?;
+        println!("Player {} connected from {}.", player, address);
+
+        let mut writer = stream.try_clone()?;
+        writeln!(writer, "WELCOME {}", player)?;
+        writeln!(writer, "RULE {}", rule_name)?;
+        writeln!(writer, "BOARD {} {}", board.size.rows, board.size.cols)?;
+        for row in board.to_rows() {
+            writeln!(writer, "{}", row)?;
+        }
+        streams.push(writer);
+
+        let sender = sender.clone();
+        std::thread::spawn(move || {
+            for line in BufReader::new(stream).lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                if sender.send((player, line)).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+    // Only the reader threads hold senders now, so the channel disconnects
+    // once every player has left.
+    drop(sender);
+
+    let mut game = Game::new(boards, rule);
+    let mut winning_numbers = vec![None; game.boards.len()];
+    for &number in drawn_numbers {
+        for winner in game.draw(number) {
+            winning_numbers[winner] = Some(number);
+        }
+        println!("Drew {}.", number);
+        broadcast(&mut streams, &format!("DRAW {}", number));
+
+        let deadline = Instant::now() + options.interval;
+        while let Some(timeout) =
+            deadline.checked_duration_since(Instant::now())
+        {
+            let (player, line) = match receiver.recv_timeout(timeout) {
+                Ok(message) => message,
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => {
+                    bail!("All players disconnected.")
+                }
+            };
+            if line.trim() != "BINGO" {
+                let _ = writeln!(streams[player], "ERROR unknown command");
+                continue;
+            }
+
+            let board = &game.boards[player];
+            if !board.complete() {
+                println!("Rejected claim from player {}.", player);
+                let _ = writeln!(streams[player], "REJECTED");
+                continue;
+            }
+            let winning_number = winning_numbers[player]
+                .ok_or(anyhow!("Board {} won without a draw.", player))?;
+            let score = board.sum_unmarked() * winning_number;
+            println!(
+                "Player {} wins by {} with a score of {}:",
+                player,
+                board.win.as_ref().unwrap(),
+                score
+            );
+            println!("{}", board.display(options.colour));
+            broadcast(&mut streams, &format!("WINNER {} {}", player, score));
+            return Ok(());
+        }
+    }
+
+    println!("Out of numbers, no one won.");
+    broadcast(&mut streams, "GAMEOVER");
+    Ok(())
+}
@@ -0,0 +1,110 @@
+use crate::{rules, Board, BoardSize, BoardValue, Game, WinRecord};
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+struct BoardState {
+    values: Vec<Vec<usize>>,
+    marked: Vec<Vec<bool>>,
+}
+
+/// The full state of a game in progress, as written to a JSON file.
+#[derive(Serialize, Deserialize)]
+struct Snapshot {
+    rule: String,
+    size: BoardSize,
+    boards: Vec<BoardState>,
+    drawn: Vec<usize>,
+    remaining: Vec<usize>,
+    wins: Vec<WinRecord>,
+}
+
+pub fn save(
+    path: &str,
+    game: &Game,
+    rule: &str,
+    remaining: &[usize],
+) -> Result<()> {
+    let size = match game.boards.first() {
+        Some(board) => board.size,
+        None => bail!("Cannot save a game without boards."),
+    };
+    let snapshot = Snapshot {
+        rule: rule.to_string(),
+        size,
+        boards: game
+            .boards
+            .iter()
+            .map(|board| BoardState {
+                values: board
+                    .values
+                    .iter()
+                    .map(|row| row.iter().map(|bv| bv.value).collect())
+                    .collect(),
+                marked: board
+                    .values
+                    .iter()
+                    .map(|row| row.iter().map(|bv| bv.marked).collect())
+                    .collect(),
+            })
+            .collect(),
+        drawn: game.drawn.clone(),
+        remaining: remaining.to_vec(),
+        wins: game.wins.clone(),
+    };
+    let file = std::fs::File::create(path)
+        .with_context(|| format!("Failed to create {}.", path))?;
+    serde_json::to_writer_pretty(std::io::BufWriter::new(file), &snapshot)
+        .context("Failed to write snapshot.")
+}
+
+fn has_shape<T>(grid: &[Vec<T>], size: BoardSize) -> bool {
+    grid.len() == size.rows && grid.iter().all(|row| row.len() == size.cols)
+}
+
+/// Restores a saved game, returning it with the numbers still to be drawn
+/// and the win rule it was played with.
+pub fn load(path: &str) -> Result<(Game, Vec<usize>, String)> {
+    let file = std::fs::File::open(path)
+        .with_context(|| format!("Failed to open {}.", path))?;
+    let snapshot: Snapshot =
+        serde_json::from_reader(std::io::BufReader::new(file))
+            .context("Failed to parse snapshot.")?;
+    let size = snapshot.size;
+
+    let mut boards = Vec::new();
+    for (i, state) in snapshot.boards.iter().enumerate() {
+        if !has_shape(&state.values, size) || !has_shape(&state.marked, size) {
+            bail!("Board {} in snapshot is not {}.", i, size);
+        }
+        boards.push(Board::new(
+            size,
+            state
+                .values
+                .iter()
+                .map(|row| row.iter().map(|&n| BoardValue::new(n)).collect())
+                .collect(),
+        ));
+    }
+
+    let mut game = Game::new(boards, rules::parse(&snapshot.rule, size)?);
+    for (board, state) in game.boards.iter_mut().zip(snapshot.boards.iter()) {
+        for (row, marked_row) in state.marked.iter().enumerate() {
+            for (col, &marked) in marked_row.iter().enumerate() {
+                if marked {
+                    board.mark_at(row, col);
+                }
+            }
+        }
+    }
+    for record in snapshot.wins.iter() {
+        let board = game.boards.get_mut(record.board).with_context(|| {
+            format!("Snapshot win refers to missing board {}.", record.board)
+        })?;
+        board.win = Some(record.win.clone());
+    }
+    game.drawn = snapshot.drawn;
+    game.wins = snapshot.wins;
+
+    Ok((game, snapshot.remaining, snapshot.rule))
+}
@@ -0,0 +1,222 @@
+use crate::Line;
+use anyhow::{bail, Result};
+use std::collections::{HashMap, HashSet};
+
+/// The smallest lattice step along a line, pointing right or, for vertical
+/// lines, down.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+struct Direction {
+    step_x: i128,
+    step_y: i128,
+}
+
+impl Direction {
+    /// Coefficients (a, b) of the supporting line a * x + b * y = intercept.
+    fn coefficients(self) -> (i128, i128) {
+        (-self.step_y, self.step_x)
+    }
+
+    fn intercept(self, (x, y): (i128, i128)) -> i128 {
+        let (a, b) = self.coefficients();
+        a * x + b * y
+    }
+
+    /// Position of a point along lines with this direction. Consecutive
+    /// lattice points on a line are `spacing` apart.
+    fn position(self, (x, y): (i128, i128)) -> i128 {
+        x * self.step_x + y * self.step_y
+    }
+
+    fn spacing(self) -> i128 {
+        self.step_x * self.step_x + self.step_y * self.step_y
+    }
+}
+
+/// A segment as an inclusive range of positions along its supporting line.
+struct Span {
+    direction: Direction,
+    intercept: i128,
+    start: i128,
+    end: i128,
+}
+
+impl Span {
+    fn new(line: &Line) -> Span {
+        let start = (line.x_1 as i128, line.y_1 as i128);
+        let end = (line.x_2 as i128, line.y_2 as i128);
+        let direction = match line.step().0 {
+            // A single point is treated as a horizontal line.
+            (0, 0) => Direction {
+                step_x: 1,
+                step_y: 0,
+            },
+            (step_x, step_y) if step_x < 0 || (step_x == 0 && step_y < 0) => {
+                Direction {
+                    step_x: -step_x,
+                    step_y: -step_y,
+                }
+            }
+            (step_x, step_y) => Direction { step_x, step_y },
+        };
+        let (a, b) = (direction.position(start), direction.position(end));
+        Span {
+            direction,
+            intercept: direction.intercept(start),
+            start: a.min(b),
+            end: a.max(b),
+        }
+    }
+
+    fn contains(&self, point: (i128, i128)) -> bool {
+        let position = self.direction.position(point);
+        self.direction.intercept(point) == self.intercept
+            && self.start <= position
+            && position <= self.end
+    }
+
+    /// Returns the lattice point where two non-parallel spans cross, if any.
+    fn crossing(&self, other: &Span) -> Option<(i128, i128)> {
+        let (a_1, b_1) = self.direction.coefficients();
+        let (a_2, b_2) = other.direction.coefficients();
+        let det = a_1 * b_2 - a_2 * b_1;
+        if det == 0 {
+            return None;
+        }
+        let x = self.intercept * b_2 - other.intercept * b_1;
+        let y = a_1 * other.intercept - a_2 * self.intercept;
+        if x % det != 0 || y % det != 0 {
+            return None;
+        }
+        let point = (x / det, y / det);
+        (self.contains(point) && other.contains(point)).then_some(point)
+    }
+}
+
+/// Merges ranges of positions `spacing` apart into the sorted, disjoint
+/// ranges covered at least twice.
+fn double_coverage(
+    ranges: &[(i128, i128)],
+    spacing: i128,
+) -> Vec<(i128, i128)> {
+    let mut events: Vec<(i128, i32)> = ranges
+        .iter()
+        .flat_map(|&(start, end)| [(start, 1), (end + spacing, -1)])
+        .collect();
+    events.sort_unstable();
+
+    let mut covered = Vec::new();
+    let mut depth = 0;
+    let mut run_start = None;
+    let mut i = 0;
+    while i < events.len() {
+        let position = events[i].0;
+        while i < events.len() && events[i].0 == position {
+            depth += events[i].1;
+            i += 1;
+        }
+        match run_start {
+            None if depth >= 2 => run_start = Some(position),
+            Some(start) if depth < 2 => {
+                covered.push((start, position - spacing));
+                run_start = None;
+            }
+            _ => (),
+        }
+    }
+    covered
+}
+
+/// Counts the lattice points covered by at least two lines without
+/// rasterizing them. Collinear lines contribute the runs where they overlap,
+/// and every other overlap is a crossing of two non-parallel lines.
+pub fn overlaps(lines: &[Line]) -> Result<u128> {
+    // Crossings are found by solving a * x + b * y = c for two lines, which
+    // multiplies coordinates by the square of the step length.
+    let max_coordinate = lines
+        .iter()
+        .flat_map(|l| [l.x_1, l.y_1, l.x_2, l.y_2])
+        .map(|c| c.unsigned_abs() as u128)
+        .max()
+        .unwrap_or(0);
+    let max_step = lines
+        .iter()
+        .map(|l| {
+            let (step_x, step_y) = l.step().0;
+            step_x.unsigned_abs().max(step_y.unsigned_abs())
+        })
+        .max()
+        .unwrap_or(0);
+    let bound = max_step
+        .checked_mul(max_step)
+        .and_then(|b| b.checked_mul(max_coordinate.max(1)))
+        .and_then(|b| b.checked_mul(8));
+    if bound.is_none_or(|b| b > i128::MAX as u128) {
+        bail!("Line coordinates are too large for the analytic solver.");
+    }
+
+    let spans: Vec<Span> = lines.iter().map(Span::new).collect();
+
+    let mut collinear: HashMap<(Direction, i128), Vec<(i128, i128)>> =
+        HashMap::new();
+    for span in spans.iter() {
+        collinear
+            .entry((span.direction, span.intercept))
+            .or_default()
+            .push((span.start, span.end));
+    }
+    let runs: HashMap<(Direction, i128), Vec<(i128, i128)>> = collinear
+        .into_iter()
+        .map(|(key, ranges)| {
+            let covered = double_coverage(&ranges, key.0.spacing());
+            (key, covered)
+        })
+        .filter(|(_, covered)| !covered.is_empty())
+        .collect();
+    let run_points: u128 = runs
+        .iter()
+        .map(|(&(direction, _), covered)| {
+            covered
+                .iter()
+                .map(|&(start, end)| {
+                    ((end - start) / direction.spacing() + 1) as u128
+                })
+                .sum::<u128>()
+        })
+        .sum();
+    let directions: HashSet<Direction> =
+        runs.keys().map(|&(direction, _)| direction).collect();
+
+    let mut crossings = HashSet::new();
+    for (i, a) in spans.iter().enumerate() {
+        for b in spans[i + 1..].iter() {
+            if let Some(point) = a.crossing(b) {
+                crossings.insert(point);
+            }
+        }
+    }
+
+    // Every point that lies in runs on more than one supporting line is also
+    // a crossing, so crossings are used to correct for double counting.
+    let mut only_crossings = 0;
+    let mut counted_twice = 0;
+    for &point in crossings.iter() {
+        let in_runs = directions
+            .iter()
+            .filter(|&&direction| {
+                let key = (direction, direction.intercept(point));
+                let position = direction.position(point);
+                runs.get(&key).is_some_and(|covered| {
+                    let i = covered.partition_point(|&(_, end)| end < position);
+                    covered.get(i).is_some_and(|&(start, _)| start <= position)
+                })
+            })
+            .count() as u128;
+        if in_runs == 0 {
+            only_crossings += 1;
+        } else {
+            counted_twice += in_runs - 1;
+        }
+    }
+
+    Ok(run_points + only_crossings - counted_twice)
+}
@@ -0,0 +1,220 @@
+use anyhow::{anyhow, bail, Context, Result};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+type Colour = [u8; 3];
+
+/// Colours that overlap counts are interpolated between, from the lowest
+/// count to the highest.
+#[derive(Clone, Debug)]
+pub struct Palette(Vec<Colour>);
+
+impl std::str::FromStr for Palette {
+    type Err = anyhow::Error;
+
+    /// Parses a preset name or a comma-separated list of hex colours, e.g.
+    /// "000000,ff0000,ffffff".
+    fn from_str(s: &str) -> Result<Palette> {
+        let colours = match s {
+            "gray" | "grey" => vec![[0, 0, 0], [255, 255, 255]],
+            "heat" => {
+                vec![[0, 0, 0], [180, 0, 0], [255, 160, 0], [255, 255, 255]]
+            }
+            "ocean" => vec![[0, 0, 32], [0, 96, 160], [160, 255, 255]],
+            _ => s
+                .split(',')
+                .map(parse_colour)
+                .collect::<Result<Vec<Colour>>>()
+                .with_context(|| format!("Unrecognized palette '{}'.", s))?,
+        };
+        if colours.len() < 2 {
+            bail!("Palette '{}' needs at least two colours.", s);
+        }
+        Ok(Palette(colours))
+    }
+}
+
+fn parse_colour(s: &str) -> Result<Colour> {
+    let hex = s.trim().trim_start_matches('#');
+    if hex.len() != 6 {
+        bail!("Colour '{}' is not six hex digits.", s);
+    }
+    let value = u32::from_str_radix(hex, 16)
+        .map_err(|_| anyhow!("Colour '{}' is not six hex digits.", s))?;
+    Ok([(value >> 16) as u8, (value >> 8) as u8, value as u8])
+}
+
+impl Palette {
+    /// Returns the colour at `t`, between 0 and 1.
+    fn colour(&self, t: f64) -> Colour {
+        let position = t.clamp(0.0, 1.0) * (self.0.len() - 1) as f64;
+        let i = (position.floor() as usize).min(self.0.len() - 2);
+        let fraction = position - i as f64;
+        let (from, to) = (self.0[i], self.0[i + 1]);
+        let mut colour = [0; 3];
+        for channel in 0..3 {
+            colour[channel] = (from[channel] as f64
+                + (to[channel] as f64 - from[channel] as f64) * fraction)
+                .round() as u8;
+        }
+        colour
+    }
+}
+
+/// How overlap counts map to intensities.
+#[derive(Copy, Clone, Debug)]
+pub enum Scale {
+    Linear,
+    Log,
+}
+
+impl std::str::FromStr for Scale {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Scale> {
+        match s {
+            "linear" => Ok(Scale::Linear),
+            "log" => Ok(Scale::Log),
+            _ => bail!("Unrecognized scale '{}'.", s),
+        }
+    }
+}
+
+impl Scale {
+    /// Maps a count to an intensity between 0 and 1.
+    fn apply(self, count: usize, max_count: usize) -> f64 {
+        if max_count == 0 {
+            return 0.0;
+        }
+        match self {
+            Scale::Linear => count as f64 / max_count as f64,
+            Scale::Log => (count as f64).ln_1p() / (max_count as f64).ln_1p(),
+        }
+    }
+}
+
+pub struct Options {
+    pub palette: Palette,
+    pub scale: Scale,
+}
+
+/// Writes counts for a `width` by `height` image, in row-major order, as a
+/// grayscale PGM if the path ends in ".pgm" and otherwise as a PNG coloured
+/// with the palette.
+pub fn write(
+    path: &str,
+    width: usize,
+    height: usize,
+    counts: &[usize],
+    options: &Options,
+) -> Result<()> {
+    let max_count = counts.iter().copied().max().unwrap_or(0);
+    let intensities = counts
+        .iter()
+        .map(|&count| options.scale.apply(count, max_count));
+
+    let file = File::create(path)
+        .with_context(|| format!("Failed to create '{}'.", path))?;
+    let mut writer = BufWriter::new(file);
+    if path.ends_with(".pgm") {
+        let pixels: Vec<u8> =
+            intensities.map(|t| (t * 255.0).round() as u8).collect();
+        write!(writer, "P5\n{} {}\n255\n", width, height)?;
+        writer.write_all(&pixels)?;
+    } else {
+        let pixels: Vec<u8> = intensities
+            .flat_map(|t| options.palette.colour(t))
+            .collect();
+        write_png(&mut writer, width, height, &pixels)?;
+    }
+    writer
+        .flush()
+        .with_context(|| format!("Failed to write '{}'.", path))
+}
+
+fn write_png(
+    writer: &mut impl Write,
+    width: usize,
+    height: usize,
+    rgb: &[u8],
+) -> Result<()> {
+    writer.write_all(b"\x89PNG\r\n\x1a\n")?;
+
+    let mut header = Vec::new();
+    header.extend((width as u32).to_be_bytes());
+    header.extend((height as u32).to_be_bytes());
+    // 8 bits per channel, RGB, default compression, filtering and no
+    // interlacing.
+    header.extend([8, 2, 0, 0, 0]);
+    write_chunk(writer, b"IHDR", &header)?;
+
+    // Each scanline is preceded by its filter type, which is always none.
+    let mut scanlines = Vec::with_capacity(height * (width * 3 + 1));
+    for row in rgb.chunks(width * 3) {
+        scanlines.push(0);
+        scanlines.extend_from_slice(row);
+    }
+    write_chunk(writer, b"IDAT", &zlib_stored(&scanlines))?;
+
+    write_chunk(writer, b"IEND", &[])
+}
+
+fn write_chunk(
+    writer: &mut impl Write,
+    kind: &[u8; 4],
+    data: &[u8],
+) -> Result<()> {
+    writer.write_all(&(data.len() as u32).to_be_bytes())?;
+    writer.write_all(kind)?;
+    writer.write_all(data)?;
+    writer.write_all(&crc32(&[kind, data]).to_be_bytes())?;
+    Ok(())
+}
+
+/// Wraps data in a zlib stream of uncompressed deflate blocks.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut stream = vec![0x78, 0x01];
+    let mut blocks = data.chunks(u16::MAX as usize).peekable();
+    if blocks.peek().is_none() {
+        stream.extend([1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        let len = block.len() as u16;
+        stream.push(last as u8);
+        stream.extend(len.to_le_bytes());
+        stream.extend((!len).to_le_bytes());
+        stream.extend_from_slice(block);
+    }
+    stream.extend(adler32(data).to_be_bytes());
+    stream
+}
+
+fn crc32(parts: &[&[u8]]) -> u32 {
+    let mut table = [0u32; 256];
+    for (n, entry) in table.iter_mut().enumerate() {
+        let mut c = n as u32;
+        for _ in 0..8 {
+            c = if c & 1 != 0 {
+                0xedb88320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+        }
+        *entry = c;
+    }
+    let mut crc = !0u32;
+    for &byte in parts.iter().flat_map(|part| part.iter()) {
+        crc = table[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8);
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
@@ -1,137 +1,669 @@
+mod analytic;
+mod image;
+
 use anyhow::{anyhow, bail, Context, Result};
+use rayon::prelude::*;
 use scan_fmt::scan_fmt;
+use std::collections::{BTreeMap, HashMap};
 use std::fs::File;
 use std::io::{BufRead, BufReader};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// Dense storage is only used when the bounding box is at most this many
+// cells, and has at most DENSE_CELLS_PER_POINT cells per rasterized point.
+const MAX_DENSE_CELLS: u128 = 1 << 24;
+const DENSE_CELLS_PER_POINT: u128 = 8;
+const MAX_IMAGE_PIXELS: u128 = 1 << 26;
+
+#[derive(Copy, Clone, Debug)]
+struct Line {
+    x_1: i64,
+    y_1: i64,
+    x_2: i64,
+    y_2: i64,
+}
 
-const GRID_SIZE: usize = 1000;
+impl Line {
+    fn diagonal(&self) -> bool {
+        self.x_1 != self.x_2 && self.y_1 != self.y_2
+    }
 
-type GridRow = Vec<usize>;
-type GridValues = Vec<GridRow>;
+    fn delta(&self) -> (i128, i128) {
+        (
+            self.x_2 as i128 - self.x_1 as i128,
+            self.y_2 as i128 - self.y_1 as i128,
+        )
+    }
+
+    /// Returns the smallest lattice step along the line and the number of
+    /// such steps between its end points.
+    fn step(&self) -> ((i128, i128), u128) {
+        let (dx, dy) = self.delta();
+        let g = gcd(dx.unsigned_abs(), dy.unsigned_abs());
+        if g == 0 {
+            ((0, 0), 0)
+        } else {
+            ((dx / g as i128, dy / g as i128), g)
+        }
+    }
+
+    /// Number of cells the line covers.
+    fn num_points(&self, raster: Rasterization) -> u128 {
+        match raster {
+            Rasterization::Exact => self.step().1 + 1,
+            Rasterization::Bresenham => {
+                let (dx, dy) = self.delta();
+                dx.unsigned_abs().max(dy.unsigned_abs()) + 1
+            }
+        }
+    }
+
+    /// Calls `visit` with every cell the line covers, from start to end.
+    fn rasterize(
+        &self,
+        raster: Rasterization,
+        mut visit: impl FnMut(i64, i64),
+    ) {
+        let (mut x, mut y) = (self.x_1 as i128, self.y_1 as i128);
+        match raster {
+            Rasterization::Exact => {
+                let ((step_x, step_y), steps) = self.step();
+                for i in 0..=steps {
+                    if i > 0 {
+                        x += step_x;
+                        y += step_y;
+                    }
+                    visit(x as i64, y as i64);
+                }
+            }
+            Rasterization::Bresenham => {
+                let (dx, dy) = self.delta();
+                let (step_x, step_y) = (dx.signum(), dy.signum());
+                let (dx, dy) = (dx.abs(), -dy.abs());
+                let mut error = dx + dy;
+                loop {
+                    visit(x as i64, y as i64);
+                    if x == self.x_2 as i128 && y == self.y_2 as i128 {
+                        break;
+                    }
+                    let error_2 = 2 * error;
+                    if error_2 >= dy {
+                        error += dy;
+                        x += step_x;
+                    }
+                    if error_2 <= dx {
+                        error += dx;
+                        y += step_y;
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn gcd(a: u128, b: u128) -> u128 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+impl std::fmt::Display for Line {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> Result<(), std::fmt::Error> {
+        write!(f, "{},{} -> {},{}", self.x_1, self.y_1, self.x_2, self.y_2)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Rasterization {
+    // Only the lattice points the line passes through.
+    Exact,
+    // One cell per step along the major axis, as chosen by Bresenham's
+    // algorithm.
+    Bresenham,
+}
+
+impl std::str::FromStr for Rasterization {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Rasterization> {
+        match s {
+            "exact" => Ok(Rasterization::Exact),
+            "bresenham" => Ok(Rasterization::Bresenham),
+            _ => bail!("Unrecognized rasterization '{}'.", s),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Backend {
+    Auto,
+    Dense,
+    Sparse,
+}
+
+impl std::str::FromStr for Backend {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Backend> {
+        match s {
+            "auto" => Ok(Backend::Auto),
+            "dense" => Ok(Backend::Dense),
+            "sparse" => Ok(Backend::Sparse),
+            _ => bail!("Unrecognized backend '{}'.", s),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Solver {
+    Raster,
+    Analytic,
+}
+
+impl std::str::FromStr for Solver {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Solver> {
+        match s {
+            "raster" => Ok(Solver::Raster),
+            "analytic" => Ok(Solver::Analytic),
+            _ => bail!("Unrecognized solver '{}'.", s),
+        }
+    }
+}
+
+/// Number of lines covering a cell, split by the kind of line.
+#[derive(Copy, Clone, Debug, Default)]
+struct CellCounts {
+    axis_aligned: usize,
+    diagonal: usize,
+}
+
+impl CellCounts {
+    fn add(&mut self, diagonal: bool, count: usize) {
+        if diagonal {
+            self.diagonal += count;
+        } else {
+            self.axis_aligned += count;
+        }
+    }
+
+    fn total(&self) -> usize {
+        self.axis_aligned + self.diagonal
+    }
+}
+
+/// Cell counts that several threads can add to at once.
+#[derive(Debug, Default)]
+struct AtomicCellCounts {
+    axis_aligned: AtomicUsize,
+    diagonal: AtomicUsize,
+}
+
+impl AtomicCellCounts {
+    fn add(&self, diagonal: bool, count: usize) {
+        let counter = if diagonal {
+            &self.diagonal
+        } else {
+            &self.axis_aligned
+        };
+        counter.fetch_add(count, Ordering::Relaxed);
+    }
+
+    fn load(&self) -> CellCounts {
+        CellCounts {
+            axis_aligned: self.axis_aligned.load(Ordering::Relaxed),
+            diagonal: self.diagonal.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Overlap counts for both parts of the puzzle.
+struct Overlaps {
+    axis_aligned: u128,
+    all: u128,
+}
+
+enum GridValues {
+    // Counts for every cell of the bounding box, in row-major order.
+    Dense(Vec<AtomicCellCounts>),
+    // Counts for occupied cells only.
+    Sparse(HashMap<(i64, i64), CellCounts>),
+}
 
 struct Grid {
     values: GridValues,
+    raster: Rasterization,
+    // Bounding box of the lines.
+    min_x: i64,
+    min_y: i64,
+    width: u128,
+    height: u128,
 }
 
 impl Grid {
-    fn new() -> Self {
-        Grid {
-            values: vec![vec![0; GRID_SIZE]; GRID_SIZE],
+    /// Creates a grid that can hold the given lines. Unless a backend is
+    /// forced, dense storage is used when the bounding box is small relative
+    /// to the number of points the lines cover.
+    fn new(
+        lines: &[Line],
+        backend: Backend,
+        raster: Rasterization,
+    ) -> Result<Self> {
+        let min_x = lines.iter().map(|l| l.x_1.min(l.x_2)).min().unwrap_or(0);
+        let max_x = lines.iter().map(|l| l.x_1.max(l.x_2)).max().unwrap_or(0);
+        let min_y = lines.iter().map(|l| l.y_1.min(l.y_2)).min().unwrap_or(0);
+        let max_y = lines.iter().map(|l| l.y_1.max(l.y_2)).max().unwrap_or(0);
+        let width = (max_x as i128 - min_x as i128 + 1) as u128;
+        let height = (max_y as i128 - min_y as i128 + 1) as u128;
+        let area = width.saturating_mul(height);
+        let num_points: u128 = lines.iter().map(|l| l.num_points(raster)).sum();
+
+        let dense = match backend {
+            Backend::Dense => {
+                if area > MAX_DENSE_CELLS {
+                    bail!(
+                        "Bounding box of {} cells is too large for dense \
+                         storage.",
+                        area
+                    );
+                }
+                true
+            }
+            Backend::Sparse => false,
+            Backend::Auto => {
+                area <= MAX_DENSE_CELLS
+                    && area <= num_points.saturating_mul(DENSE_CELLS_PER_POINT)
+            }
+        };
+
+        Ok(Grid {
+            values: if dense {
+                GridValues::Dense(
+                    (0..area).map(|_| AtomicCellCounts::default()).collect(),
+                )
+            } else {
+                GridValues::Sparse(HashMap::new())
+            },
+            raster,
+            min_x,
+            min_y,
+            width,
+            height,
+        })
+    }
+
+    fn increment(&mut self, x: i64, y: i64, diagonal: bool) {
+        match &mut self.values {
+            GridValues::Dense(values) => {
+                let column = (x - self.min_x) as usize;
+                let row = (y - self.min_y) as usize;
+                values[row * self.width as usize + column].add(diagonal, 1);
+            }
+            GridValues::Sparse(values) => {
+                values.entry((x, y)).or_default().add(diagonal, 1)
+            }
         }
     }
 
-    fn add_line(
-        &mut self,
-        x_1: usize,
-        y_1: usize,
-        x_2: usize,
-        y_2: usize,
-        ignore_diagonals: bool,
-    ) -> Result<()> {
-        if ignore_diagonals && x_1 != x_2 && y_1 != y_2 {
+    fn add_line(&mut self, line: &Line) {
+        let diagonal = line.diagonal();
+        line.rasterize(self.raster, |x, y| self.increment(x, y, diagonal));
+    }
+
+    /// Rasterizes the lines on `threads` threads. Dense grids are updated in
+    /// place through their atomic counters and sparse grids merge per-thread
+    /// maps, so the result is the same as adding the lines one by one.
+    fn add_lines(&mut self, lines: &[Line], threads: usize) -> Result<()> {
+        if threads <= 1 {
+            for line in lines {
+                self.add_line(line);
+            }
             return Ok(());
         }
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .context("Failed to start thread pool.")?;
 
-        let horizontal_range: Box<dyn Iterator<Item = _>> = if x_1 > x_2 {
-            Box::new((x_2..=x_1).rev())
-        } else {
-            Box::new(x_1..=x_2)
-        };
-        let vertical_range: Box<dyn Iterator<Item = _>> = if y_1 > y_2 {
-            Box::new((y_2..=y_1).rev())
-        } else {
-            Box::new(y_1..=y_2)
+        let (min_x, min_y, width) = (self.min_x, self.min_y, self.width);
+        let raster = self.raster;
+        match &mut self.values {
+            GridValues::Dense(values) => {
+                let values = &*values;
+                pool.install(|| {
+                    lines.par_iter().for_each(|line| {
+                        let diagonal = line.diagonal();
+                        line.rasterize(raster, |x, y| {
+                            let column = (x - min_x) as usize;
+                            let row = (y - min_y) as usize;
+                            values[row * width as usize + column]
+                                .add(diagonal, 1);
+                        });
+                    })
+                });
+            }
+            GridValues::Sparse(values) => {
+                let chunk_size = lines.len().div_ceil(threads).max(1);
+                let partials: Vec<HashMap<(i64, i64), CellCounts>> = pool
+                    .install(|| {
+                        lines
+                            .par_chunks(chunk_size)
+                            .map(|chunk| {
+                                let mut partial: HashMap<_, CellCounts> =
+                                    HashMap::new();
+                                for line in chunk {
+                                    let diagonal = line.diagonal();
+                                    line.rasterize(raster, |x, y| {
+                                        partial
+                                            .entry((x, y))
+                                            .or_default()
+                                            .add(diagonal, 1);
+                                    });
+                                }
+                                partial
+                            })
+                            .collect()
+                    });
+                for partial in partials {
+                    for (cell, counts) in partial {
+                        let total = values.entry(cell).or_default();
+                        total.add(false, counts.axis_aligned);
+                        total.add(true, counts.diagonal);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the number of lines covering each cell of the bounding box as
+    /// an image.
+    fn write_image(&self, path: &str, options: &image::Options) -> Result<()> {
+        let area = self.width.saturating_mul(self.height);
+        if area > MAX_IMAGE_PIXELS {
+            bail!("Bounding box of {} cells is too large for an image.", area);
+        }
+        let (width, height) = (self.width as usize, self.height as usize);
+        let counts: Vec<usize> = match &self.values {
+            GridValues::Dense(values) => {
+                values.iter().map(|counts| counts.load().total()).collect()
+            }
+            GridValues::Sparse(values) => {
+                let mut totals = vec![0; width * height];
+                for (&(x, y), counts) in values.iter() {
+                    let column = (x - self.min_x) as usize;
+                    let row = (y - self.min_y) as usize;
+                    totals[row * width + column] = counts.total();
+                }
+                totals
+            }
         };
+        image::write(path, width, height, &counts, options)
+    }
 
-        let normal_diagonal = if x_2 > x_1 { x_2 - x_1 } else { x_1 - x_2 }
-            == if y_2 > y_1 { y_2 - y_1 } else { y_1 - y_2 };
-
-        if y_1 == y_2 {
-            for x in horizontal_range {
-                *(self
-                    .values
-                    .get_mut(y_1)
-                    .ok_or_else(|| {
-                        anyhow!(
-                            "Vertical coordinate {} in {},{} -> {},{} exceeds grid size.",
-                            y_1, x_1, y_1, x_2, y_2
-                        )
-                    })?
-                    .get_mut(x)
-                    .ok_or_else(|| {
-                        anyhow!(
-                            "Horizontal coordinate {} in {},{} -> {},{} exceeds grid size.",
-                            x, x_1, y_1, x_2, y_2
-                        )
-                    })?) += 1;
-            }
-        } else if x_1 == x_2 {
-            for y in vertical_range {
-                *(self
-                    .values
-                    .get_mut(y)
-                    .ok_or_else(|| {
-                        anyhow!(
-                            "Vertical coordinate {} in {},{} -> {},{} exceeds grid size.",
-                            y, x_1, y_1, x_2, y_2)
-                    })?
-                    .get_mut(x_1)
-                    .ok_or_else(|| {
-                        anyhow!(
-                            "Horizontal coordinate {} in {},{} -> {},{} exceeds grid size.",
-                            x_1, x_1, y_1, x_2, y_2
-                        )
-                    })?) += 1;
-            }
-        } else if normal_diagonal {
-            for (y, x) in vertical_range.zip(horizontal_range) {
-                *(self
-                    .values
-                    .get_mut(y)
-                    .ok_or_else(|| {
-                        anyhow!(
-                            "Vertical coordinate {} in {},{} -> {},{} exceeds grid size.",
-                            y, x_1, y_1, x_2, y_2
-                        )
-                    })?
-                    .get_mut(x)
-                    .ok_or_else(|| {
-                        anyhow!(
-                            "Horizontal coordinate {} in {},{} -> {},{} exceeds grid size.",
-                            x_1, x_1, y_1, x_2, y_2
-                        )
-                    })?) += 1;
+    /// Iterates over the coordinates and counts of every cell covered by at
+    /// least one line.
+    fn cells(&self) -> Box<dyn Iterator<Item = ((i64, i64), CellCounts)> + '_> {
+        match &self.values {
+            GridValues::Dense(values) => {
+                let width = self.width as usize;
+                Box::new(
+                    values
+                        .iter()
+                        .map(|counts| counts.load())
+                        .enumerate()
+                        .filter(|(_, counts)| counts.total() > 0)
+                        .map(move |(i, counts)| {
+                            let x = self.min_x + (i % width) as i64;
+                            let y = self.min_y + (i / width) as i64;
+                            ((x, y), counts)
+                        }),
+                )
+            }
+            GridValues::Sparse(values) => {
+                Box::new(values.iter().map(|(&cell, &counts)| (cell, counts)))
             }
-        } else {
-            bail!(
-                "Unrecognized line type in {},{} -> {},{}.",
-                x_1,
-                y_1,
-                x_2,
-                y_2
-            );
         }
+    }
 
-        Ok(())
+    /// Number of lines covering the given cell.
+    fn count_at(&self, x: i64, y: i64) -> usize {
+        match &self.values {
+            GridValues::Dense(values) => {
+                let column = x as i128 - self.min_x as i128;
+                let row = y as i128 - self.min_y as i128;
+                if column < 0
+                    || row < 0
+                    || column as u128 >= self.width
+                    || row as u128 >= self.height
+                {
+                    return 0;
+                }
+                values[row as usize * self.width as usize + column as usize]
+                    .load()
+                    .total()
+            }
+            GridValues::Sparse(values) => {
+                values.get(&(x, y)).map_or(0, |counts| counts.total())
+            }
+        }
+    }
+
+    /// Number of cells covered by at least `k` lines. Cells that no line
+    /// covers are never counted.
+    fn count_at_least(&self, k: usize) -> usize {
+        self.cells()
+            .filter(|(_, counts)| counts.total() >= k.max(1))
+            .count()
+    }
+
+    /// Returns the `n` cells covered by the most lines, ordered by count and
+    /// then by position.
+    fn hotspots(&self, n: usize) -> Vec<((i64, i64), usize)> {
+        let mut cells: Vec<((i64, i64), usize)> = self
+            .cells()
+            .map(|(cell, counts)| (cell, counts.total()))
+            .collect();
+        cells.sort_unstable_by_key(|&((x, y), count)| {
+            (std::cmp::Reverse(count), y, x)
+        });
+        cells.truncate(n);
+        cells
     }
 
-    fn overlaps(&self) -> usize {
+    /// Maps each count to the number of cells covered by exactly that many
+    /// lines.
+    fn histogram(&self) -> BTreeMap<usize, usize> {
+        let mut histogram = BTreeMap::new();
+        for (_, counts) in self.cells() {
+            *histogram.entry(counts.total()).or_default() += 1;
+        }
+        histogram
+    }
+
+    /// Number of the line's cells that are also covered by another line.
+    fn segment_overlaps(&self, line: &Line) -> usize {
         let mut overlaps = 0;
-        for row in self.values.iter() {
-            for &value in row.iter() {
-                if value > 1 {
-                    overlaps += 1;
-                }
+        line.rasterize(self.raster, |x, y| {
+            if self.count_at(x, y) > 1 {
+                overlaps += 1;
+            }
+        });
+        overlaps
+    }
+
+    fn overlaps(&self) -> Overlaps {
+        let mut overlaps = Overlaps {
+            axis_aligned: 0,
+            all: 0,
+        };
+        for (_, counts) in self.cells() {
+            if counts.axis_aligned > 1 {
+                overlaps.axis_aligned += 1;
+            }
+            if counts.total() > 1 {
+                overlaps.all += 1;
             }
         }
         overlaps
     }
 }
 
+struct Options {
+    filename: String,
+    backend: Backend,
+    solver: Solver,
+    raster: Rasterization,
+    image_path: Option<String>,
+    image: image::Options,
+    at_least: Option<usize>,
+    hotspots: Option<usize>,
+    histogram: bool,
+    segments: bool,
+    threads: usize,
+}
+
+impl Options {
+    fn parse(args: impl Iterator<Item = String>) -> Result<Options> {
+        let mut args = args.skip(1);
+        let mut filename = None;
+        let mut options = Options {
+            filename: String::new(),
+            backend: Backend::Auto,
+            solver: Solver::Raster,
+            raster: Rasterization::Exact,
+            image_path: None,
+            image: image::Options {
+                palette: "heat".parse()?,
+                scale: image::Scale::Linear,
+            },
+            at_least: None,
+            hotspots: None,
+            histogram: false,
+            segments: false,
+            threads: std::thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1),
+        };
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--backend" => {
+                    options.backend = args
+                        .next()
+                        .ok_or(anyhow!("No backend provided."))?
+                        .parse()?;
+                }
+                "--solver" => {
+                    options.solver = args
+                        .next()
+                        .ok_or(anyhow!("No solver provided."))?
+                        .parse()?;
+                }
+                "--raster" => {
+                    options.raster = args
+                        .next()
+                        .ok_or(anyhow!("No rasterization provided."))?
+                        .parse()?;
+                }
+                "--image" => {
+                    options.image_path = Some(
+                        args.next()
+                            .ok_or(anyhow!("No image path provided."))?,
+                    );
+                }
+                "--palette" => {
+                    options.image.palette = args
+                        .next()
+                        .ok_or(anyhow!("No palette provided."))?
+                        .parse()?;
+                }
+                "--scale" => {
+                    options.image.scale = args
+                        .next()
+                        .ok_or(anyhow!("No scale provided."))?
+                        .parse()?;
+                }
+                "--at-least" => {
+                    options.at_least = Some(parse_count(args.next())?);
+                }
+                "--hotspots" => {
+                    options.hotspots = Some(parse_count(args.next())?);
+                }
+                "--histogram" => options.histogram = true,
+                "--segments" => options.segments = true,
+                "--threads" => options.threads = parse_count(args.next())?,
+                _ if arg.starts_with("--") => {
+                    bail!("Unrecognized option '{}'.", arg)
+                }
+                _ if filename.is_some() => {
+                    bail!("Unexpected argument '{}'.", arg)
+                }
+                _ => filename = Some(arg),
+            }
+        }
+        options.filename = filename.ok_or(anyhow!("No filename provided."))?;
+        Ok(options)
+    }
+
+    /// Whether any requested output needs the rasterized grid.
+    fn needs_grid(&self) -> bool {
+        self.image_path.is_some()
+            || self.at_least.is_some()
+            || self.hotspots.is_some()
+            || self.histogram
+            || self.segments
+    }
+}
+
+fn parse_count(arg: Option<String>) -> Result<usize> {
+    let count_str = arg.ok_or(anyhow!("No count provided."))?;
+    count_str.parse::<usize>().with_context(|| {
+        format!("Failed to parse '{}' as unsigned integer.", count_str)
+    })
+}
+
+fn print_queries(grid: &Grid, lines: &[Line], options: &Options) {
+    if let Some(k) = options.at_least {
+        println!(
+            "Cells covered at least {} times: {}",
+            k,
+            grid.count_at_least(k)
+        );
+    }
+    if let Some(n) = options.hotspots {
+        println!("Hotspots:");
+        for ((x, y), count) in grid.hotspots(n) {
+            println!("{},{}\t{}", x, y, count);
+        }
+    }
+    if options.histogram {
+        println!("count\tcells");
+        for (count, cells) in grid.histogram() {
+            println!("{}\t{}", count, cells);
+        }
+    }
+    if options.segments {
+        println!("segment\toverlapping\tcells");
+        for line in lines {
+            println!(
+                "{}\t{}\t{}",
+                line,
+                grid.segment_overlaps(line),
+                line.num_points(grid.raster)
+            );
+        }
+    }
+}
+
 fn main() -> Result<()> {
-    let filename = std::env::args()
-        .nth(1)
-        .ok_or(anyhow!("No filename provided."))?;
-    let file = File::open(filename).context("Failed to open file.")?;
+    let options = Options::parse(std::env::args())?;
+    let file = File::open(&options.filename).context("Failed to open file.")?;
     let reader = BufReader::new(file);
 
     let lines: Vec<String> = reader
@@ -139,18 +671,49 @@ fn main() -> Result<()> {
         .collect::<Result<Vec<String>, _>>()
         .context("Failed to read line.")?;
 
-    let mut grid = Grid::new();
+    let mut vent_lines = Vec::new();
     for line in lines.iter() {
         if let Ok((x_1, y_1, x_2, y_2)) =
-            scan_fmt!(line, "{d},{d} -> {d},{d}", usize, usize, usize, usize)
+            scan_fmt!(line, "{d},{d} -> {d},{d}", i64, i64, i64, i64)
         {
-            grid.add_line(x_1, y_1, x_2, y_2, false)?;
+            vent_lines.push(Line { x_1, y_1, x_2, y_2 });
         } else {
             bail!("Line invalid: {}", line);
         }
     }
 
-    println!("Overlaps: {}", grid.overlaps());
+    let overlaps = match options.solver {
+        Solver::Raster => {
+            let mut grid =
+                Grid::new(&vent_lines, options.backend, options.raster)?;
+            grid.add_lines(&vent_lines, options.threads)?;
+            if let Some(path) = options.image_path.as_deref() {
+                grid.write_image(path, &options.image)?;
+            }
+            print_queries(&grid, &vent_lines, &options);
+            grid.overlaps()
+        }
+        Solver::Analytic => {
+            if options.raster != Rasterization::Exact {
+                bail!("The analytic solver only supports exact rasterization.");
+            }
+            if options.needs_grid() {
+                bail!("Images and queries need the raster solver.");
+            }
+            let axis_aligned: Vec<Line> = vent_lines
+                .iter()
+                .filter(|line| !line.diagonal())
+                .copied()
+                .collect();
+            Overlaps {
+                axis_aligned: analytic::overlaps(&axis_aligned)?,
+                all: analytic::overlaps(&vent_lines)?,
+            }
+        }
+    };
+
+    println!("Overlaps without diagonals: {}", overlaps.axis_aligned);
+    println!("Overlaps: {}", overlaps.all);
 
     Ok(())
 }
@@ -1,13 +1,396 @@
-use std::collections::VecDeque;
+mod bigint;
+mod matrix;
+mod model;
+mod rng;
+mod series;
+mod stochastic;
 
 use anyhow::{anyhow, bail, Context, Result};
+use bigint::BigUint;
+use matrix::Matrix;
+use model::{Model, Transition};
+use series::SeriesWriter;
+use stochastic::ResetDistribution;
+
+/// Arithmetic needed to advance fish counts.
+trait Number: Clone {
+    fn add(&self, other: &Self) -> Self;
+    fn mul(&self, other: &Self) -> Self;
+}
+
+/// A fish count that is kept in a `u128` until it overflows, and in an
+/// arbitrary-precision integer after that.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Count {
+    Small(u128),
+    Big(BigUint),
+}
+
+impl Count {
+    fn big(&self) -> BigUint {
+        match self {
+            Count::Small(value) => BigUint::from(*value),
+            Count::Big(value) => value.clone(),
+        }
+    }
+}
+
+impl Number for Count {
+    fn add(&self, other: &Count) -> Count {
+        if let (Count::Small(a), Count::Small(b)) = (self, other) {
+            if let Some(sum) = a.checked_add(*b) {
+                return Count::Small(sum);
+            }
+        }
+        Count::Big(&self.big() + &other.big())
+    }
+
+    fn mul(&self, other: &Count) -> Count {
+        if let (Count::Small(a), Count::Small(b)) = (self, other) {
+            if let Some(product) = a.checked_mul(*b) {
+                return Count::Small(product);
+            }
+        }
+        Count::Big(&self.big() * &other.big())
+    }
+}
+
+impl std::fmt::Display for Count {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> Result<(), std::fmt::Error> {
+        match self {
+            Count::Small(value) => write!(f, "{}", value),
+            Count::Big(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+/// A fish count modulo `modulus`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct Modular {
+    value: u64,
+    modulus: u64,
+}
+
+impl Modular {
+    fn new(value: u64, modulus: u64) -> Modular {
+        Modular {
+            value: value % modulus,
+            modulus,
+        }
+    }
+}
+
+impl Number for Modular {
+    fn add(&self, other: &Modular) -> Modular {
+        let sum =
+            (self.value as u128 + other.value as u128) % self.modulus as u128;
+        Modular::new(sum as u64, self.modulus)
+    }
+
+    fn mul(&self, other: &Modular) -> Modular {
+        let product =
+            (self.value as u128 * other.value as u128) % self.modulus as u128;
+        Modular::new(product as u64, self.modulus)
+    }
+}
+
+impl std::fmt::Display for Modular {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> Result<(), std::fmt::Error> {
+        write!(f, "{}", self.value)
+    }
+}
+
+// Runs that advance the counts one day at a time, with the iterative engine,
+// to write a series or in a stochastic simulation, are rejected past this many
+// days since they would take too long to finish.
+const MAX_ITERATIVE_DAYS: u64 = 10_000_000;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Engine {
+    Iterative,
+    Matrix,
+}
+
+impl std::str::FromStr for Engine {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Engine> {
+        match s {
+            "iterative" => Ok(Engine::Iterative),
+            "matrix" => Ok(Engine::Matrix),
+            _ => bail!("Unrecognized engine '{}'.", s),
+        }
+    }
+}
+
+struct Options {
+    filename: String,
+    days: Vec<u64>,
+    engine: Engine,
+    model: Model,
+    modulus: Option<u64>,
+    series: Option<String>,
+    growth: bool,
+    trials: Option<usize>,
+    seed: u64,
+    threads: usize,
+    reset: Option<ResetDistribution>,
+}
+
+fn next_arg<T: std::str::FromStr>(
+    args: &mut impl Iterator<Item = String>,
+    what: &str,
+) -> Result<T>
+where
+    T::Err: Into<anyhow::Error>,
+{
+    let arg = args.next().ok_or(anyhow!("No {} provided.", what))?;
+    arg.parse::<T>()
+        .map_err(Into::into)
+        .with_context(|| format!("Failed to parse '{}' as {}.", arg, what))
+}
+
+impl Options {
+    fn parse(args: impl Iterator<Item = String>) -> Result<Options> {
+        let mut args = args.skip(1);
+        let mut filename = None;
+        let mut days = Vec::new();
+        let mut engine = None;
+        let mut model = None;
+        let mut modulus = None;
+        let mut series = None;
+        let mut growth = false;
+        let mut trials = None;
+        let mut seed = 0;
+        let mut threads = std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1);
+        let mut reset = None;
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--days" => {
+                    let days_str: String = next_arg(&mut args, "days")?;
+                    for d in days_str.split(',') {
+                        days.push(d.parse::<u64>().with_context(|| {
+                            format!("Failed to parse '{}' as days.", d)
+                        })?);
+                    }
+                }
+                "--engine" => engine = Some(next_arg(&mut args, "engine")?),
+                "--model" => {
+                    let name_or_path: String = next_arg(&mut args, "model")?;
+                    model = Some(Model::load(&name_or_path)?);
+                }
+                "--modulus" => {
+                    let value = next_arg(&mut args, "modulus")?;
+                    if value == 0 {
+                        bail!("Modulus must be at least 1.");
+                    }
+                    modulus = Some(value);
+                }
+                "--series" => {
+                    series = Some(next_arg(&mut args, "series path")?)
+                }
+                "--growth" => growth = true,
+                "--stochastic" => {
+                    trials = Some(next_arg(&mut args, "number of trials")?)
+                }
+                "--seed" => seed = next_arg(&mut args, "seed")?,
+                "--threads" => {
+                    threads = next_arg(&mut args, "number of threads")?
+                }
+                "--reset-timers" => {
+                    reset = Some(next_arg(&mut args, "reset distribution")?)
+                }
+                _ if arg.starts_with("--") => {
+                    bail!("Unrecognized option '{}'.", arg)
+                }
+                _ if filename.is_some() => {
+                    bail!("Unexpected argument '{}'.", arg)
+                }
+                _ => filename = Some(arg),
+            }
+        }
+        if days.is_empty() {
+            days.push(256);
+        }
+        days.sort_unstable();
+        days.dedup();
+
+        let last_day = *days.last().unwrap();
+        if trials.is_some() {
+            if engine.is_some()
+                || growth
+                || modulus.is_some()
+                || series.is_some()
+            {
+                bail!(
+                    "Stochastic simulations do not support --engine, \
+                     --growth, --modulus or --series."
+                );
+            }
+            if last_day > MAX_ITERATIVE_DAYS {
+                bail!(
+                    "Simulating {} days one at a time would not finish.",
+                    last_day
+                );
+            }
+        }
+
+        // Counts modulo a number stay small, so raising the transition matrix
+        // to a large power is cheap.
+        let engine = engine.unwrap_or(if modulus.is_some() {
+            Engine::Matrix
+        } else {
+            Engine::Iterative
+        });
+        if trials.is_none()
+            && engine == Engine::Iterative
+            && last_day > MAX_ITERATIVE_DAYS
+        {
+            bail!(
+                "Simulating {} days one at a time would not finish, use \
+                 --engine matrix.",
+                last_day
+            );
+        }
+        if series.is_some() && last_day > MAX_ITERATIVE_DAYS {
+            bail!(
+                "Writing a series of {} days would not finish, the series \
+                 is computed one day at a time.",
+                last_day
+            );
+        }
+
+        Ok(Options {
+            filename: filename.ok_or(anyhow!("No filename provided."))?,
+            days,
+            engine,
+            model: model.map_or_else(|| Model::load("lanternfish"), Ok)?,
+            modulus,
+            series,
+            growth,
+            trials,
+            seed,
+            threads,
+            reset,
+        })
+    }
+}
+
+/// Advances the state counts one day at a time.
+fn simulate<T: Number>(
+    mut counts: Vec<T>,
+    transitions: &[(Transition, T)],
+    zero: &T,
+    days: u64,
+) -> Vec<T> {
+    for _ in 0..days {
+        let mut next = vec![zero.clone(); counts.len()];
+        for (transition, multiplier) in transitions {
+            let to = &mut next[transition.to];
+            *to = to.add(&multiplier.mul(&counts[transition.from]));
+        }
+        counts = next;
+    }
+    counts
+}
+
+/// The matrix that advances the state counts by one day.
+fn transition_matrix<T: Number>(
+    num_states: usize,
+    transitions: &[(Transition, T)],
+    zero: &T,
+) -> Matrix<T> {
+    Matrix::from_fn(num_states, |to, from| {
+        transitions
+            .iter()
+            .filter(|(t, _)| t.to == to && t.from == from)
+            .fold(zero.clone(), |sum, (_, multiplier)| sum.add(multiplier))
+    })
+}
+
+/// Prints the fish count after each requested number of days, using
+/// `convert` to turn integers into the chosen kind of count. When a series is
+/// requested, the counts are advanced one day at a time so that every day
+/// can be written.
+fn report<T: Number + std::fmt::Display>(
+    options: &Options,
+    initial_counts: &[u64],
+    convert: impl Fn(u64) -> T,
+) -> Result<()> {
+    let model = &options.model;
+    let zero = convert(0);
+    let mut counts: Vec<T> =
+        initial_counts.iter().map(|&n| convert(n)).collect();
+    let transitions: Vec<(Transition, T)> = model
+        .transitions()
+        .into_iter()
+        .map(|t| (t, convert(t.multiplier)))
+        .collect();
+    let modulus = options
+        .modulus
+        .map(|modulus| format!(" mod {}", modulus))
+        .unwrap_or_default();
+
+    let total = |counts: &[T]| {
+        counts
+            .iter()
+            .fold(zero.clone(), |total, count| total.add(count))
+    };
+
+    let mut series = match options.series.as_deref() {
+        Some(path) => {
+            let mut series = SeriesWriter::new(path, &model.state_labels())?;
+            series.row(0, &counts, &total(&counts))?;
+            Some(series)
+        }
+        None => None,
+    };
+
+    let mut day = 0;
+    for &report_day in options.days.iter() {
+        if let Some(series) = series.as_mut() {
+            while day < report_day {
+                counts = simulate(counts, &transitions, &zero, 1);
+                day += 1;
+                series.row(day, &counts, &total(&counts))?;
+            }
+        }
+        counts = match options.engine {
+            Engine::Iterative => {
+                simulate(counts, &transitions, &zero, report_day - day)
+            }
+            Engine::Matrix => {
+                transition_matrix(model.num_states(), &transitions, &zero)
+                    .apply_power(report_day - day, &counts)
+            }
+        };
+        day = report_day;
+        println!(
+            "Fish count after {} days{}: {}",
+            day,
+            modulus,
+            total(&counts)
+        );
+    }
+
+    if let Some(series) = series {
+        series.finish()?;
+    }
+    Ok(())
+}
 
 fn main() -> Result<()> {
-    let filename = std::env::args()
-        .nth(1)
-        .ok_or(anyhow!("No filename provided."))?;
-    let line =
-        std::fs::read_to_string(filename).context("Failed to open file.")?;
+    let options = Options::parse(std::env::args())?;
+    let line = std::fs::read_to_string(&options.filename)
+        .context("Failed to open file.")?;
     let initial_timers = line
         .trim_end()
         .split(",")
@@ -17,27 +400,65 @@ fn main() -> Result<()> {
         })
         .collect::<Result<Vec<_>>>()?;
 
-    const MAX_TIMER_VALUE: usize = 8;
-    let mut timer_value_count_map =
-        VecDeque::from(vec![0; MAX_TIMER_VALUE + 1]);
-    for initial_timer in initial_timers {
-        if initial_timer > MAX_TIMER_VALUE {
-            bail!("{} exceeded maximum timer value.", initial_timer);
+    let model = &options.model;
+    if let Some(trials) = options.trials {
+        let stochastic_options = stochastic::Options {
+            trials,
+            seed: options.seed,
+            threads: options.threads,
+            reset: options
+                .reset
+                .clone()
+                .unwrap_or(ResetDistribution::fixed(model.cycle - 1)),
+        };
+        let summaries = stochastic::simulate(
+            model,
+            &initial_timers,
+            &options.days,
+            &stochastic_options,
+        )?;
+        for summary in summaries {
+            println!(
+                "Fish count after {} days over {} trials: mean {:.2}, \
+                 variance {:.2}",
+                summary.day, trials, summary.mean, summary.variance
+            );
+            println!(
+                "  percentiles: {}",
+                summary
+                    .percentiles
+                    .iter()
+                    .map(|(percentile, population)| {
+                        format!("{}% {}", percentile, population)
+                    })
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            );
         }
-        timer_value_count_map[initial_timer] += 1;
+        return Ok(());
+    }
+
+    let mut initial_counts = vec![0; model.num_states()];
+    for initial_timer in initial_timers {
+        initial_counts[model.state_for_timer(initial_timer)?] += 1;
     }
 
-    const SIMULATION_DAYS: usize = 256;
-    for _ in 0..SIMULATION_DAYS {
-        let num_expired_timers = timer_value_count_map.pop_front().unwrap();
-        *(timer_value_count_map.get_mut(6).unwrap()) += num_expired_timers;
-        timer_value_count_map.push_back(num_expired_timers);
+    match options.modulus {
+        None => report(&options, &initial_counts, |n| Count::Small(n as u128))?,
+        Some(modulus) => {
+            report(&options, &initial_counts, |n| Modular::new(n, modulus))?
+        }
     }
 
-    println!(
-        "Fish count: {}",
-        timer_value_count_map.iter().sum::<usize>()
-    );
+    if options.growth {
+        let factor = model.growth_factor();
+        println!("Growth factor per day: {:.6}", factor);
+        if factor > 1.0 {
+            println!("Doubling time: {:.3} days", 2f64.ln() / factor.ln());
+        } else {
+            println!("Doubling time: never, the population does not grow");
+        }
+    }
 
     Ok(())
 }
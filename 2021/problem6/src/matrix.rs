@@ -0,0 +1,56 @@
+use crate::Number;
+
+/// A square matrix, stored as rows.
+#[derive(Clone, Debug)]
+pub struct Matrix<T>(Vec<Vec<T>>);
+
+impl<T: Number> Matrix<T> {
+    pub fn from_fn(size: usize, f: impl Fn(usize, usize) -> T) -> Matrix<T> {
+        Matrix(
+            (0..size)
+                .map(|row| (0..size).map(|col| f(row, col)).collect())
+                .collect(),
+        )
+    }
+
+    fn size(&self) -> usize {
+        self.0.len()
+    }
+
+    fn mul(&self, other: &Matrix<T>) -> Matrix<T> {
+        Matrix::from_fn(self.size(), |row, col| {
+            (1..self.size())
+                .fold(self.0[row][0].mul(&other.0[0][col]), |sum, k| {
+                    sum.add(&self.0[row][k].mul(&other.0[k][col]))
+                })
+        })
+    }
+
+    pub fn apply(&self, vector: &[T]) -> Vec<T> {
+        self.0
+            .iter()
+            .map(|row| {
+                (1..vector.len()).fold(row[0].mul(&vector[0]), |sum, k| {
+                    sum.add(&row[k].mul(&vector[k]))
+                })
+            })
+            .collect()
+    }
+
+    /// Returns `self` raised to the power `n` applied to `vector`, using
+    /// repeated squaring.
+    pub fn apply_power(&self, mut n: u64, vector: &[T]) -> Vec<T> {
+        let mut vector = vector.to_vec();
+        let mut power = self.clone();
+        while n > 0 {
+            if n & 1 == 1 {
+                vector = power.apply(&vector);
+            }
+            n >>= 1;
+            if n > 0 {
+                power = power.mul(&power);
+            }
+        }
+        vector
+    }
+}
@@ -0,0 +1,243 @@
+use anyhow::{anyhow, bail, Context, Result};
+
+/// An age-structured population model. Fish first reproduce
+/// `cycle + newborn_delay` days after birth and then every `cycle` days,
+/// each time producing `offspring` newborns. Fish that reach `death_age`
+/// die without reproducing that day.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Model {
+    pub cycle: usize,
+    pub newborn_delay: usize,
+    pub offspring: u64,
+    pub death_age: Option<usize>,
+}
+
+/// A contribution of `multiplier` fish in state `to` for each fish in state
+/// `from`, from one day to the next.
+#[derive(Copy, Clone, Debug)]
+pub struct Transition {
+    pub from: usize,
+    pub to: usize,
+    pub multiplier: u64,
+}
+
+const PRESETS: [(&str, Model); 4] = [
+    (
+        "lanternfish",
+        Model {
+            cycle: 7,
+            newborn_delay: 2,
+            offspring: 1,
+            death_age: None,
+        },
+    ),
+    (
+        "twins",
+        Model {
+            cycle: 7,
+            newborn_delay: 2,
+            offspring: 2,
+            death_age: None,
+        },
+    ),
+    (
+        "mortal",
+        Model {
+            cycle: 7,
+            newborn_delay: 2,
+            offspring: 1,
+            death_age: Some(50),
+        },
+    ),
+    (
+        "slow",
+        Model {
+            cycle: 10,
+            newborn_delay: 4,
+            offspring: 1,
+            death_age: None,
+        },
+    ),
+];
+
+impl Model {
+    /// Returns the named preset, or loads a config file of `key = value`
+    /// lines. Keys are `cycle`, `newborn_delay`, `offspring` and
+    /// `death_age`, and any that are missing keep their lanternfish value.
+    pub fn load(name_or_path: &str) -> Result<Model> {
+        if let Some((_, model)) =
+            PRESETS.iter().find(|(name, _)| *name == name_or_path)
+        {
+            return Ok(model.clone());
+        }
+        let config =
+            std::fs::read_to_string(name_or_path).with_context(|| {
+                format!(
+                    "'{}' is neither a model preset nor a readable file.",
+                    name_or_path
+                )
+            })?;
+        Model::parse(&config).with_context(|| {
+            format!("Failed to load model '{}'.", name_or_path)
+        })
+    }
+
+    fn parse(config: &str) -> Result<Model> {
+        let mut model = PRESETS[0].1.clone();
+        for (i, line) in config.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .map(|(key, value)| (key.trim(), value.trim()))
+                .ok_or(anyhow!("Line {} is not 'key = value'.", i + 1))?;
+            let value = value.parse::<u64>().with_context(|| {
+                format!("Failed to parse '{}' as unsigned integer.", value)
+            })?;
+            match key {
+                "cycle" => model.cycle = value as usize,
+                "newborn_delay" => model.newborn_delay = value as usize,
+                "offspring" => model.offspring = value,
+                "death_age" => model.death_age = Some(value as usize),
+                _ => bail!("Unrecognized model key '{}'.", key),
+            }
+        }
+        if model.cycle == 0 {
+            bail!("Cycle length must be at least 1.");
+        }
+        if model.death_age == Some(0) {
+            bail!("Death age must be at least 1.");
+        }
+        Ok(model)
+    }
+
+    /// Age at which a fish first reproduces.
+    fn first_spawn_age(&self) -> usize {
+        self.cycle + self.newborn_delay
+    }
+
+    /// Number of states. Without a death age, fish past their first
+    /// reproduction share states with younger fish at the same point of the
+    /// cycle.
+    pub fn num_states(&self) -> usize {
+        self.death_age.unwrap_or(self.first_spawn_age())
+    }
+
+    /// Largest timer an initial fish may have.
+    pub fn max_timer(&self) -> usize {
+        self.first_spawn_age() - 1
+    }
+
+    /// Returns the state of an initial fish, which is taken to be as young as
+    /// its timer allows. States are numbered from the oldest age down, so
+    /// without a death age a fish's state is its timer.
+    pub fn state_for_timer(&self, timer: usize) -> Result<usize> {
+        if timer > self.max_timer() {
+            bail!("{} exceeded maximum timer value.", timer);
+        }
+        let age = self.max_timer() - timer;
+        if age >= self.num_states() {
+            bail!("Fish with timer {} would already be dead.", timer);
+        }
+        Ok(self.num_states() - 1 - age)
+    }
+
+    /// Names the states, by timer without a death age and by age otherwise.
+    pub fn state_labels(&self) -> Vec<String> {
+        let states = self.num_states();
+        (0..states)
+            .map(|state| match self.death_age {
+                None => format!("timer_{}", state),
+                Some(_) => format!("age_{}", states - 1 - state),
+            })
+            .collect()
+    }
+
+    /// Returns the asymptotic factor by which the population grows each day,
+    /// the dominant eigenvalue of the transition matrix. For this kind of
+    /// (Leslie) matrix it is the root of the Euler-Lotka equation: the sum of
+    /// `offspring * factor^-age` over the ages at which fish reproduce is 1.
+    pub fn growth_factor(&self) -> f64 {
+        let first = self.first_spawn_age() as i32;
+        let cycle = self.cycle as i32;
+        let offspring = self.offspring as f64;
+        let spawn_ages: Vec<i32> = match self.death_age {
+            Some(death_age) => {
+                (first..death_age as i32).step_by(self.cycle).collect()
+            }
+            // Unbounded, and summed in closed form below.
+            None => vec![first],
+        };
+        if self.offspring == 0 || spawn_ages.is_empty() {
+            return if self.death_age.is_some() { 0.0 } else { 1.0 };
+        }
+
+        // Decreasing in the factor, for factors above `low`.
+        let offspring_sum = |factor: f64| match self.death_age {
+            Some(_) => spawn_ages
+                .iter()
+                .map(|&age| offspring * factor.powi(-age))
+                .sum::<f64>(),
+            // Fish reproduce at every age first + k * cycle.
+            None => {
+                offspring * factor.powi(-first) / (1.0 - factor.powi(-cycle))
+            }
+        };
+        let mut low = if self.death_age.is_some() { 0.0 } else { 1.0 };
+        let mut high: f64 = 2.0;
+        while offspring_sum(high) > 1.0 {
+            low = high;
+            high *= 2.0;
+        }
+        for _ in 0..200 {
+            let middle = (low + high) / 2.0;
+            if offspring_sum(middle) > 1.0 {
+                low = middle;
+            } else {
+                high = middle;
+            }
+        }
+        (low + high) / 2.0
+    }
+
+    /// Transitions that advance the population by one day.
+    pub fn transitions(&self) -> Vec<Transition> {
+        let states = self.num_states();
+        let state = |age: usize| states - 1 - age;
+        let mut transitions = Vec::new();
+        for age in 0..states {
+            let next_age = age + 1;
+            let spawns = next_age >= self.first_spawn_age()
+                && (next_age - self.first_spawn_age())
+                    .is_multiple_of(self.cycle);
+            match self.death_age {
+                Some(death_age) if next_age >= death_age => continue,
+                Some(_) => transitions.push(Transition {
+                    from: state(age),
+                    to: state(next_age),
+                    multiplier: 1,
+                }),
+                // Past the first reproduction, ages repeat every cycle.
+                None => transitions.push(Transition {
+                    from: state(age),
+                    to: state(if spawns {
+                        next_age - self.cycle
+                    } else {
+                        next_age
+                    }),
+                    multiplier: 1,
+                }),
+            }
+            if spawns {
+                transitions.push(Transition {
+                    from: state(age),
+                    to: state(0),
+                    multiplier: self.offspring,
+                });
+            }
+        }
+        transitions
+    }
+}
@@ -0,0 +1,33 @@
+/// SplitMix64, used to draw the random reset timers. Each trial gets its own
+/// generator, seeded from the run's seed and the trial's index.
+pub struct Rng {
+    state: u64,
+}
+
+fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+impl Rng {
+    pub fn for_trial(seed: u64, trial: u64) -> Rng {
+        Rng {
+            state: mix(seed ^ mix(trial)),
+        }
+    }
+
+    /// Returns a uniformly distributed value in `[0, 1)`.
+    pub fn next_f64(&mut self) -> f64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        (mix(self.state) >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Returns a standard normally distributed value, by the Box-Muller
+    /// transform. Using `1 - u` keeps the logarithm finite.
+    pub fn normal(&mut self) -> f64 {
+        let u = 1.0 - self.next_f64();
+        let v = self.next_f64();
+        (-2.0 * u.ln()).sqrt() * (2.0 * std::f64::consts::PI * v).cos()
+    }
+}
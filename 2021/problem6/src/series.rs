@@ -0,0 +1,89 @@
+use anyhow::{Context, Result};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Format {
+    Csv,
+    Json,
+}
+
+/// Writes the state counts and total population for each day, as JSON if the
+/// path ends in ".json" and as CSV otherwise.
+pub struct SeriesWriter {
+    path: String,
+    writer: BufWriter<File>,
+    format: Format,
+    rows: usize,
+}
+
+impl SeriesWriter {
+    pub fn new(path: &str, labels: &[String]) -> Result<SeriesWriter> {
+        let file = File::create(path)
+            .with_context(|| format!("Failed to create '{}'.", path))?;
+        let mut series = SeriesWriter {
+            path: path.to_string(),
+            writer: BufWriter::new(file),
+            format: if path.ends_with(".json") {
+                Format::Json
+            } else {
+                Format::Csv
+            },
+            rows: 0,
+        };
+        match series.format {
+            Format::Csv => {
+                writeln!(series.writer, "day,{},total", labels.join(","))
+            }
+            Format::Json => write!(
+                series.writer,
+                "{{\"states\":[{}],\"days\":[",
+                labels
+                    .iter()
+                    .map(|label| format!("\"{}\"", label))
+                    .collect::<Vec<String>>()
+                    .join(",")
+            ),
+        }
+        .with_context(|| format!("Failed to write '{}'.", series.path))?;
+        Ok(series)
+    }
+
+    pub fn row(
+        &mut self,
+        day: u64,
+        counts: &[impl std::fmt::Display],
+        total: &impl std::fmt::Display,
+    ) -> Result<()> {
+        let counts = counts
+            .iter()
+            .map(|count| count.to_string())
+            .collect::<Vec<String>>()
+            .join(",");
+        match self.format {
+            Format::Csv => {
+                writeln!(self.writer, "{},{},{}", day, counts, total)
+            }
+            Format::Json => write!(
+                self.writer,
+                "{}{{\"day\":{},\"counts\":[{}],\"total\":{}}}",
+                if self.rows > 0 { "," } else { "" },
+                day,
+                counts,
+                total
+            ),
+        }
+        .with_context(|| format!("Failed to write '{}'.", self.path))?;
+        self.rows += 1;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<()> {
+        if self.format == Format::Json {
+            writeln!(self.writer, "]}}")?;
+        }
+        self.writer
+            .flush()
+            .with_context(|| format!("Failed to write '{}'.", self.path))
+    }
+}
@@ -0,0 +1,269 @@
+use crate::model::Model;
+use crate::rng::Rng;
+use anyhow::{anyhow, bail, Context, Result};
+
+// Binomial draws with more trials than this use a normal approximation, if
+// their variance is at least `MIN_NORMAL_VARIANCE`.
+const EXACT_BINOMIAL_LIMIT: u64 = 1000;
+const MIN_NORMAL_VARIANCE: f64 = 25.0;
+
+const PERCENTILES: [u32; 5] = [5, 25, 50, 75, 95];
+
+/// Probabilities of the timer a fish is reset to after reproducing.
+#[derive(Clone, Debug)]
+pub struct ResetDistribution(Vec<(usize, f64)>);
+
+impl std::str::FromStr for ResetDistribution {
+    type Err = anyhow::Error;
+
+    /// Parses comma-separated `timer:weight` pairs such as "6:3,7:1".
+    /// Weights are normalized to sum to 1.
+    fn from_str(s: &str) -> Result<ResetDistribution> {
+        let mut outcomes = Vec::new();
+        for pair in s.split(',') {
+            let (timer, weight) = pair.split_once(':').ok_or(anyhow!(
+                "Reset timer '{}' is not 'timer:weight'.",
+                pair
+            ))?;
+            let timer = timer.trim().parse::<usize>().with_context(|| {
+                format!("Failed to parse '{}' as unsigned integer.", timer)
+            })?;
+            let weight = weight.trim().parse::<f64>().with_context(|| {
+                format!("Failed to parse '{}' as a weight.", weight)
+            })?;
+            if !(weight >= 0.0 && weight.is_finite()) {
+                bail!("Weight {} for timer {} is not valid.", weight, timer);
+            }
+            outcomes.push((timer, weight));
+        }
+        let total: f64 = outcomes.iter().map(|&(_, weight)| weight).sum();
+        if total <= 0.0 {
+            bail!("Reset timer weights in '{}' sum to zero.", s);
+        }
+        Ok(ResetDistribution(
+            outcomes
+                .into_iter()
+                .map(|(timer, weight)| (timer, weight / total))
+                .collect(),
+        ))
+    }
+}
+
+impl ResetDistribution {
+    /// Always resets to the same timer.
+    pub fn fixed(timer: usize) -> ResetDistribution {
+        ResetDistribution(vec![(timer, 1.0)])
+    }
+
+    fn max_timer(&self) -> usize {
+        self.0.iter().map(|&(timer, _)| timer).max().unwrap_or(0)
+    }
+}
+
+pub struct Options {
+    pub trials: usize,
+    pub seed: u64,
+    pub threads: usize,
+    pub reset: ResetDistribution,
+}
+
+/// Statistics of the population across trials on one day.
+pub struct Summary {
+    pub day: u64,
+    pub mean: f64,
+    pub variance: f64,
+    pub percentiles: Vec<(u32, u64)>,
+}
+
+/// Samples the number of successes in `n` trials with probability `p`.
+fn binomial(rng: &mut Rng, n: u64, p: f64) -> u64 {
+    let variance = n as f64 * p * (1.0 - p);
+    if p <= 0.0 || n == 0 {
+        0
+    } else if p >= 1.0 {
+        n
+    } else if p > 0.5 {
+        n - binomial(rng, n, 1.0 - p)
+    } else if n <= EXACT_BINOMIAL_LIMIT {
+        (0..n).filter(|_| rng.next_f64() < p).count() as u64
+    } else if variance < MIN_NORMAL_VARIANCE {
+        binomial_inversion(rng, n, p)
+    } else {
+        let mean = n as f64 * p;
+        (mean + variance.sqrt() * rng.normal())
+            .round()
+            .clamp(0.0, n as f64) as u64
+    }
+}
+
+/// Samples a binomial by walking up its cumulative distribution, which takes
+/// about `n * p` steps and so suits draws with a small mean.
+fn binomial_inversion(rng: &mut Rng, n: u64, p: f64) -> u64 {
+    let u = rng.next_f64();
+    let ratio = p / (1.0 - p);
+    let mut k = 0;
+    let mut probability = (n as f64 * (-p).ln_1p()).exp();
+    let mut cumulative = probability;
+    // Rounding can leave the cumulative sum just short of 1, so stop once the
+    // remaining probabilities are too small to represent.
+    while u >= cumulative && k < n && probability > 0.0 {
+        probability *= ratio * (n - k) as f64 / (k + 1) as f64;
+        k += 1;
+        cumulative += probability;
+    }
+    k
+}
+
+/// Runs one simulation, returning the population on each of the given days.
+fn trial(
+    model: &Model,
+    initial_counts: &[u64],
+    days: &[u64],
+    reset: &ResetDistribution,
+    rng: &mut Rng,
+) -> Result<Vec<u64>> {
+    let newborn_timer = model.max_timer();
+    let mut counts = initial_counts.to_vec();
+    let mut populations = Vec::with_capacity(days.len());
+    let mut day = 0;
+    for &report_day in days {
+        while day < report_day {
+            let expired = counts[0];
+            counts.rotate_left(1);
+            *counts.last_mut().unwrap() = 0;
+
+            // Split the expired fish between reset timers, one outcome at a
+            // time.
+            let mut remaining = expired;
+            let mut remaining_probability = 1.0;
+            for (i, &(timer, probability)) in reset.0.iter().enumerate() {
+                let reset_count = if i == reset.0.len() - 1 {
+                    remaining
+                } else {
+                    binomial(
+                        rng,
+                        remaining,
+                        probability / remaining_probability,
+                    )
+                };
+                counts[timer] += reset_count;
+                remaining -= reset_count;
+                remaining_probability -= probability;
+            }
+
+            counts[newborn_timer] = expired
+                .checked_mul(model.offspring)
+                .and_then(|born| counts[newborn_timer].checked_add(born))
+                .ok_or(anyhow!("Population overflowed on day {}.", day + 1))?;
+            day += 1;
+        }
+        let population = counts
+            .iter()
+            .try_fold(0u64, |total, &count| total.checked_add(count))
+            .ok_or(anyhow!("Population overflowed on day {}.", day))?;
+        populations.push(population);
+    }
+    Ok(populations)
+}
+
+fn summarize(day: u64, mut populations: Vec<u64>) -> Summary {
+    populations.sort_unstable();
+    let n = populations.len() as f64;
+    let mean = populations.iter().map(|&p| p as f64).sum::<f64>() / n;
+    let variance = if populations.len() > 1 {
+        populations
+            .iter()
+            .map(|&p| (p as f64 - mean).powi(2))
+            .sum::<f64>()
+            / (n - 1.0)
+    } else {
+        0.0
+    };
+    // Nearest-rank percentiles.
+    let percentiles = PERCENTILES
+        .iter()
+        .map(|&percentile| {
+            let rank = (percentile as f64 / 100.0 * n).ceil().max(1.0);
+            (percentile, populations[rank as usize - 1])
+        })
+        .collect();
+    Summary {
+        day,
+        mean,
+        variance,
+        percentiles,
+    }
+}
+
+/// Simulates the population `options.trials` times with reset timers drawn
+/// from the distribution, and summarizes the population on each of the given
+/// days. Every trial draws from its own generator and the populations are
+/// summarized in trial order, so the results do not depend on the number of
+/// threads.
+pub fn simulate(
+    model: &Model,
+    initial_timers: &[usize],
+    days: &[u64],
+    options: &Options,
+) -> Result<Vec<Summary>> {
+    if model.death_age.is_some() {
+        bail!("Stochastic simulations do not support a death age.");
+    }
+    if options.trials == 0 {
+        bail!("Stochastic simulations need at least one trial.");
+    }
+    let num_timers = options.reset.max_timer().max(model.max_timer()) + 1;
+    let mut initial_counts = vec![0; num_timers];
+    for &timer in initial_timers {
+        if timer > model.max_timer() {
+            bail!("{} exceeded maximum timer value.", timer);
+        }
+        initial_counts[timer] += 1;
+    }
+
+    let trials = options.trials;
+    let threads = options.threads.clamp(1, trials);
+    let mut by_trial = vec![Vec::new(); trials];
+    std::thread::scope(|scope| -> Result<()> {
+        let handles: Vec<_> = (0..threads)
+            .map(|t| {
+                let initial_counts = &initial_counts;
+                scope.spawn(move || {
+                    (t..trials)
+                        .step_by(threads)
+                        .map(|i| {
+                            let mut rng =
+                                Rng::for_trial(options.seed, i as u64);
+                            let populations = trial(
+                                model,
+                                initial_counts,
+                                days,
+                                &options.reset,
+                                &mut rng,
+                            )?;
+                            Ok((i, populations))
+                        })
+                        .collect::<Result<Vec<_>>>()
+                })
+            })
+            .collect();
+        for handle in handles {
+            for (i, populations) in handle.join().unwrap()? {
+                by_trial[i] = populations;
+            }
+        }
+        Ok(())
+    })?;
+
+    let mut populations = vec![Vec::with_capacity(trials); days.len()];
+    for trial_populations in by_trial {
+        for (i, population) in trial_populations.into_iter().enumerate() {
+            populations[i].push(population);
+        }
+    }
+    Ok(days
+        .iter()
+        .zip(populations)
+        .map(|(&day, populations)| summarize(day, populations))
+        .collect())
+}